@@ -22,23 +22,42 @@
 
 //! `jutella` CLI interface configuration.
 
+use crate::{
+    estimate::Prices,
+    macros::{self, Macros},
+    preferences::{Preferences, Units},
+};
 use anyhow::{anyhow, Context as _};
-use clap::Parser;
-use dirs::home_dir;
-use jutella::Auth;
-use std::{fs, path::PathBuf};
+use clap::{Parser, Subcommand};
+use dirs::{config_dir, home_dir};
+use jutella::{
+    Auth, ContextStrategy, EntraIdCredential, OllamaOptions, Provider, Proxy, RateLimits,
+    ResponseFormat, RetryPolicy, Temperature, Tls, TopP,
+};
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf, time::Duration};
 
-const HOME_CONFIG_LOCATION: &str = ".config/jutella.toml";
-const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1/";
-const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const ENV_PREFIX: &str = "JUTELLA_";
+/// Config dir used on all platforms before the platform-specific ones, relative to home.
+const LEGACY_CONFIG_DIR: &str = ".config";
+const CONFIG_LOCATION: &str = "jutella.toml";
+const MEMORIES_LOCATION: &str = "jutella/memories.json";
+const BENCHMARKS_LOCATION: &str = "jutella/benchmarks.jsonl";
+const FEEDBACK_LOCATION: &str = "jutella/feedback.jsonl";
+const FAVORITES_LOCATION: &str = "jutella/favorites.json";
+
+/// Requests incognito exchanges stay in the context for by default.
+const DEFAULT_INCOGNITO_TURNS: usize = 5;
 
 #[derive(Debug, Parser)]
 #[command(version)]
 #[command(about = "Chatbot API CLI. Currently supports OpenAI chat API.", long_about = None)]
-#[command(after_help = "You can only set API key/token in the config. \
-                        Command line options override the ones in the config.")]
+#[command(after_help = "You can only set API key/token in the config or with \
+                        `JUTELLA_API_KEY` / `JUTELLA_API_TOKEN` environment variables. \
+                        Environment variables `JUTELLA_API_URL`, `JUTELLA_API_VERSION`, \
+                        `JUTELLA_MODEL` and `JUTELLA_SYSTEM_MESSAGE` override the config. \
+                        Command line options override both.")]
 pub struct Args {
-    /// Base API url. Default: "https://api.openai.com/v1/".
+    /// Base API url. Default: the URL of the configured provider, "https://api.openai.com/v1/".
     #[arg(short = 'u', long)]
     api_url: Option<String>,
 
@@ -46,7 +65,8 @@ pub struct Args {
     #[arg(short, long)]
     api_version: Option<String>,
 
-    /// Model. Default: "gpt-4o-mini". You likely need to include the version date.
+    /// Model. Default: the default model of the configured provider, "gpt-4o-mini".
+    /// You likely need to include the version date.
     #[arg(short, long)]
     model: Option<String>,
 
@@ -54,7 +74,9 @@ pub struct Args {
     #[arg(short, long)]
     system_message: Option<String>,
 
-    /// Config file location. Default: "$HOME/.config/jutella.toml".
+    /// Config file location. Default: "jutella.toml" in the platform config dir, e.g.,
+    /// "$XDG_CONFIG_HOME" or "$HOME/.config" on Linux, optional if the API key is set in
+    /// the environment.
     #[arg(short, long)]
     config: Option<PathBuf>,
 
@@ -75,40 +97,291 @@ pub struct Args {
     /// Keep at most that many tokens in the conversation context.
     #[arg(short = 't', long)]
     max_history_tokens: Option<usize>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Model related commands.
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Config related commands.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Run a guided conversation flow defined in a TOML or JSON file.
+    Flow {
+        /// Flow definition, JSON if the extension is `.json`, TOML otherwise.
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the config and the remembered facts as a portable JSON bundle.
+    Export {
+        /// Omit `api_key` and `api_token` from the bundle.
+        #[arg(long)]
+        no_secrets: bool,
+    },
+    /// Import a bundle created with `jutella config export`.
+    ///
+    /// The current config is backed up to `<config>.bak`. If the bundle has no API key/token,
+    /// the current ones are kept. Remembered facts are merged.
+    Import {
+        /// Bundle file, "-" to read from stdin.
+        bundle: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ModelsCommand {
+    /// Measure latency and output tokens per second of the models.
+    ///
+    /// The results are appended to the benchmarks file to track the performance over time.
+    Benchmark {
+        /// Models to benchmark. Default: the configured model.
+        models: Vec<String>,
+
+        /// Number of requests to send to every model.
+        #[arg(short, long, default_value_t = 5)]
+        runs: usize,
+    },
 }
 
 impl Args {
     pub fn parse() -> Self {
         <Args as Parser>::parse()
     }
+
+    /// Take the `config` subcommand, which doesn't need a valid configuration to run.
+    pub fn take_config_command(&mut self) -> Option<ConfigCommand> {
+        match self.command.take() {
+            Some(CliCommand::Config { command }) => Some(command),
+            command => {
+                self.command = command;
+                None
+            }
+        }
+    }
+
+    /// Config file location.
+    pub fn config_path(&self) -> anyhow::Result<PathBuf> {
+        self.config
+            .clone()
+            .ok_or(())
+            .or_else(|()| standard_location(CONFIG_LOCATION))
+    }
 }
 
-#[derive(Debug, serde::Deserialize)]
+/// Location of the file `name` in the platform config dir: `$XDG_CONFIG_HOME` or
+/// `~/.config` on Linux, `~/Library/Application Support` on macOS, `%APPDATA%` on Windows.
+///
+/// A file only present in the legacy location `~/.config` is copied to the platform config dir,
+/// leaving the original in place. If copying fails, the legacy location is used.
+fn standard_location(name: &str) -> anyhow::Result<PathBuf> {
+    let path = config_dir()
+        .ok_or(anyhow!(
+            "Config dir missing, cannot locate {name} in standard location"
+        ))?
+        .join(name);
+
+    let Some(legacy) = home_dir().map(|home| home.join(LEGACY_CONFIG_DIR).join(name)) else {
+        return Ok(path);
+    };
+
+    if legacy == path || path.exists() || !legacy.exists() {
+        return Ok(path);
+    }
+
+    let copied = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::copy(&legacy, &path));
+    match copied {
+        Ok(_) => {
+            eprintln!(
+                "Copied {} to {}, the old file is no longer used and can be removed",
+                legacy.display(),
+                path.display()
+            );
+            Ok(path)
+        }
+        Err(e) => {
+            eprintln!(
+                "Failed to copy {} to {}: {e}",
+                legacy.display(),
+                path.display()
+            );
+            Ok(legacy)
+        }
+    }
+}
+
+/// Standard location of the remembered facts.
+pub fn default_memories_file() -> anyhow::Result<PathBuf> {
+    standard_location(MEMORIES_LOCATION)
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
 struct ConfigFile {
+    provider: Option<Provider>,
     api_url: Option<String>,
     api_version: Option<String>,
+    fallback_api_urls: Option<Vec<String>>,
+    max_retries: Option<usize>,
+    connect_timeout: Option<u64>,
+    proxy: Option<String>,
+    proxy_username: Option<String>,
+    proxy_password: Option<String>,
+    ca_certificate: Option<PathBuf>,
+    client_certificate: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    requests_per_minute: Option<usize>,
+    tokens_per_minute: Option<usize>,
     api_key: Option<String>,
     api_token: Option<String>,
+    entra_id: Option<EntraIdConfig>,
     model: Option<String>,
+    validate_model: Option<bool>,
+    temperature: Option<Temperature>,
+    top_p: Option<TopP>,
+    max_completion_tokens: Option<usize>,
+    frequency_penalty: Option<f32>,
+    presence_penalty: Option<f32>,
+    seed: Option<i64>,
+    response_format: Option<ResponseFormat>,
     system_message: Option<String>,
+    locale: Option<String>,
+    units: Option<Units>,
     min_history_tokens: Option<usize>,
     max_history_tokens: Option<usize>,
+    context_strategy: Option<ContextStrategy>,
+    confirm_above_tokens: Option<usize>,
+    incognito_turns: Option<usize>,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+    prices: Option<Prices>,
     xclip: Option<bool>,
+    memories_file: Option<PathBuf>,
+    benchmarks_file: Option<PathBuf>,
+    feedback_file: Option<PathBuf>,
+    favorites_file: Option<PathBuf>,
+    hyperlinks: Option<bool>,
+    webhook_url: Option<String>,
+    webhook_errors_only: Option<bool>,
+    ollama: Option<OllamaOptions>,
+    headers: Option<BTreeMap<String, String>>,
+    commands: Option<Macros>,
+}
+
+/// Microsoft Entra ID credential: client secret of an app registration if `client_secret`
+/// is set, managed identity otherwise.
+#[derive(Debug, serde::Deserialize)]
+struct EntraIdConfig {
+    tenant_id: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+impl TryFrom<EntraIdConfig> for EntraIdCredential {
+    type Error = anyhow::Error;
+
+    fn try_from(config: EntraIdConfig) -> anyhow::Result<Self> {
+        match config {
+            EntraIdConfig {
+                tenant_id: Some(tenant_id),
+                client_id: Some(client_id),
+                client_secret: Some(client_secret),
+            } => Ok(EntraIdCredential::ClientSecret {
+                tenant_id,
+                client_id,
+                client_secret,
+            }),
+            EntraIdConfig {
+                tenant_id: None,
+                client_id,
+                client_secret: None,
+            } => Ok(EntraIdCredential::ManagedIdentity { client_id }),
+            _ => Err(anyhow!(
+                "`entra_id` needs `tenant_id`, `client_id` and `client_secret`, \
+                 or only an optional `client_id` of a managed identity"
+            )),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Override the options with `JUTELLA_*` environment variables.
+    fn apply_env(&mut self) {
+        let var = |name: &str| env::var(format!("{ENV_PREFIX}{name}")).ok();
+
+        // Credentials from the environment replace the ones from the config entirely.
+        let (api_key, api_token) = (var("API_KEY"), var("API_TOKEN"));
+        if api_key.is_some() || api_token.is_some() {
+            self.api_key = api_key;
+            self.api_token = api_token;
+            self.entra_id = None;
+        }
+
+        self.api_url = var("API_URL").or(self.api_url.take());
+        self.api_version = var("API_VERSION").or(self.api_version.take());
+        self.model = var("MODEL").or(self.model.take());
+        self.system_message = var("SYSTEM_MESSAGE").or(self.system_message.take());
+    }
 }
 
 pub struct Configuration {
+    pub provider: Provider,
     pub api_url: String,
     pub api_version: Option<String>,
+    pub fallback_api_urls: Vec<String>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub rate_limits: Option<RateLimits>,
+    pub connect_timeout: Option<Duration>,
+    pub proxy: Option<Proxy>,
+    pub tls: Tls,
+    pub headers: Vec<(String, String)>,
     pub auth: Auth,
     pub model: String,
+    pub validate_model: bool,
+    pub temperature: Option<Temperature>,
+    pub top_p: Option<TopP>,
+    pub max_completion_tokens: Option<usize>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub seed: Option<i64>,
+    pub response_format: Option<ResponseFormat>,
     pub system_message: Option<String>,
+    pub preferences: Preferences,
     pub min_history_tokens: Option<usize>,
     pub max_history_tokens: Option<usize>,
+    pub context_strategy: ContextStrategy,
+    pub confirm_above_tokens: Option<usize>,
+    pub incognito_turns: usize,
+    /// Prices of the models, including the configured one if set.
+    pub prices: Prices,
     pub xclip: bool,
+    pub memories_file: PathBuf,
+    pub benchmarks_file: PathBuf,
+    pub feedback_file: PathBuf,
+    pub favorites_file: PathBuf,
+    pub hyperlinks: bool,
+    pub webhook_url: Option<String>,
+    pub webhook_errors_only: bool,
+    pub ollama_options: OllamaOptions,
+    pub macros: Macros,
+    pub command: Option<CliCommand>,
 }
 
 impl Configuration {
     pub fn init(args: Args) -> anyhow::Result<Self> {
+        let config_path = args.config_path()?;
         let Args {
             api_url,
             api_version,
@@ -118,49 +391,105 @@ impl Configuration {
             max_history_tokens,
             config,
             xclip,
+            command,
         } = args;
 
-        let config_path = config.ok_or(()).or_else(|()| {
-            home_dir()
-                .ok_or(anyhow!(
-                    "Home dir missing, cannot read config from standard location"
+        let explicit_config = config.is_some();
+
+        let mut config: ConfigFile = match fs::read_to_string(config_path.clone()) {
+            Ok(config) => toml::from_str(&config).with_context(|| {
+                anyhow!(
+                    "failed to parse config file {}",
+                    config_path.to_str().unwrap_or_default()
+                )
+            })?,
+            // The config in the standard location is optional, e.g., in containers.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit_config => {
+                ConfigFile::default()
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    anyhow!(
+                        "Failed to read config file {}",
+                        config_path.to_str().unwrap_or_default()
+                    )
+                })
+            }
+        };
+
+        config.apply_env();
+
+        let api_url = api_url.or(config.api_url);
+
+        // Without an explicit provider, it's inferred from the API URL.
+        let provider = config
+            .provider
+            .or_else(|| api_url.as_deref().and_then(Provider::from_api_url))
+            .unwrap_or_default();
+
+        let api_url = api_url.unwrap_or_else(|| String::from(provider.api_url()));
+
+        let auth = match (config.api_token, config.api_key, config.entra_id) {
+            (Some(token), None, None) => Auth::Token(token),
+            (None, Some(api_key), None) if provider.uses_api_key_header(&api_url) => {
+                Auth::ApiKey(api_key)
+            }
+            // Providers other than Azure expect the API key as a bearer token.
+            (None, Some(api_key), None) => Auth::Token(api_key),
+            (None, None, Some(entra_id)) => Auth::EntraId(entra_id.try_into()?),
+            // Local Ollama server needs no credentials.
+            (None, None, None) if provider == Provider::Ollama => Auth::None,
+            _ => {
+                return Err(anyhow!(
+                    "Exactly one of `api_key`, `api_token` or `entra_id` must be set in config \
+                     or `JUTELLA_API_KEY` / `JUTELLA_API_TOKEN` in environment"
                 ))
-                .map(|p| p.join(HOME_CONFIG_LOCATION))
-        })?;
-
-        let config = fs::read_to_string(config_path.clone()).with_context(|| {
-            anyhow!(
-                "Failed to read config file {}",
-                config_path.to_str().unwrap_or_default()
-            )
-        })?;
-
-        let config: ConfigFile = toml::from_str(&config).with_context(|| {
-            anyhow!(
-                "failed to parse config file {}",
-                config_path.to_str().unwrap_or_default()
-            )
-        })?;
-
-        let auth = match (config.api_token, config.api_key) {
-            (Some(token), None) => Auth::Token(token),
-            (None, Some(api_key)) => Auth::ApiKey(api_key),
+            }
+        };
+
+        let credentials = match (config.proxy_username, config.proxy_password) {
+            (Some(username), Some(password)) => Some((username, password)),
+            (None, None) => None,
             _ => {
                 return Err(anyhow!(
-                    "Exactly one of `api_key` or `api_token` must be set in config"
+                    "`proxy_username` and `proxy_password` must be set together"
                 ))
             }
         };
+        let proxy = match (config.proxy, credentials) {
+            (Some(url), credentials) => Some(Proxy { url, credentials }),
+            (None, None) => None,
+            (None, Some(_)) => return Err(anyhow!("Proxy credentials set without `proxy`")),
+        };
 
-        let api_url = api_url
-            .or(config.api_url)
-            .unwrap_or_else(|| String::from(DEFAULT_ENDPOINT));
+        let read_pem = |path: PathBuf| {
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+        };
+        let tls = Tls {
+            root_certificates: config.ca_certificate.map(read_pem).transpose()?,
+            client_certificate: match (config.client_certificate, config.client_key) {
+                (Some(certificate), Some(key)) => Some((read_pem(certificate)?, read_pem(key)?)),
+                (None, None) => None,
+                _ => {
+                    return Err(anyhow!(
+                        "`client_certificate` and `client_key` must be set together"
+                    ))
+                }
+            },
+        };
 
         let api_version = api_version.or(config.api_version);
 
         let model = model
             .or(config.model)
-            .unwrap_or_else(|| String::from(DEFAULT_MODEL));
+            .unwrap_or_else(|| String::from(provider.default_model()));
+
+        let mut prices = config.prices.unwrap_or_default();
+        if config.input_price.is_some() || config.output_price.is_some() {
+            let price = prices.entry(model.clone()).or_default();
+            price.input = config.input_price.or(price.input);
+            price.output = config.output_price.or(price.output);
+        }
 
         let system_message = system_message.or(config.system_message);
 
@@ -173,15 +502,146 @@ impl Configuration {
             config.xclip.unwrap_or_default()
         };
 
+        let memories_file = config
+            .memories_file
+            .ok_or(())
+            .or_else(|()| default_memories_file())?;
+
+        let benchmarks_file = config
+            .benchmarks_file
+            .ok_or(())
+            .or_else(|()| standard_location(BENCHMARKS_LOCATION))?;
+
+        let feedback_file = config
+            .feedback_file
+            .ok_or(())
+            .or_else(|()| standard_location(FEEDBACK_LOCATION))?;
+
+        let favorites_file = config
+            .favorites_file
+            .ok_or(())
+            .or_else(|()| standard_location(FAVORITES_LOCATION))?;
+
+        if config.requests_per_minute == Some(0) || config.tokens_per_minute == Some(0) {
+            return Err(anyhow!(
+                "`requests_per_minute` and `tokens_per_minute` must be positive"
+            ));
+        }
+
+        let macros = config.commands.unwrap_or_default();
+        macros::validate(&macros).context("Invalid `commands` in the config")?;
+
         Ok(Self {
+            provider,
             api_url,
             api_version,
+            fallback_api_urls: config.fallback_api_urls.unwrap_or_default(),
+            retry_policy: config.max_retries.map(|max_retries| RetryPolicy {
+                max_retries,
+                ..Default::default()
+            }),
+            rate_limits: (config.requests_per_minute.is_some()
+                || config.tokens_per_minute.is_some())
+            .then_some(RateLimits {
+                requests_per_minute: config.requests_per_minute,
+                tokens_per_minute: config.tokens_per_minute,
+            }),
+            connect_timeout: config.connect_timeout.map(Duration::from_secs),
+            proxy,
+            tls,
+            headers: config.headers.unwrap_or_default().into_iter().collect(),
             auth,
             model,
+            validate_model: config.validate_model.unwrap_or(false),
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_completion_tokens: config.max_completion_tokens,
+            frequency_penalty: config.frequency_penalty,
+            presence_penalty: config.presence_penalty,
+            seed: config.seed,
+            response_format: config.response_format,
             system_message,
+            preferences: Preferences {
+                locale: config.locale,
+                units: config.units,
+            },
             min_history_tokens,
             max_history_tokens,
+            context_strategy: config.context_strategy.unwrap_or_default(),
+            confirm_above_tokens: config.confirm_above_tokens,
+            incognito_turns: config.incognito_turns.unwrap_or(DEFAULT_INCOGNITO_TURNS),
+            prices,
             xclip,
+            memories_file,
+            benchmarks_file,
+            feedback_file,
+            favorites_file,
+            hyperlinks: config.hyperlinks.unwrap_or(true),
+            webhook_url: config.webhook_url,
+            webhook_errors_only: config.webhook_errors_only.unwrap_or(false),
+            ollama_options: config.ollama.unwrap_or_default(),
+            macros,
+            command,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_file_and_cli_overrides_env() {
+        let dir = std::env::temp_dir().join(format!("jutella-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("jutella.toml");
+        let file = |name: &str| dir.join(name).display().to_string();
+        fs::write(
+            &path,
+            format!(
+                "api_token = \"file-token\"\n\
+                 api_url = \"https://file.example/v1/\"\n\
+                 api_version = \"file-version\"\n\
+                 model = \"file-model\"\n\
+                 system_message = \"file-message\"\n\
+                 memories_file = {:?}\n\
+                 benchmarks_file = {:?}\n\
+                 feedback_file = {:?}\n\
+                 favorites_file = {:?}\n",
+                file("memories.json"),
+                file("benchmarks.jsonl"),
+                file("feedback.jsonl"),
+                file("favorites.json"),
+            ),
+        )
+        .unwrap();
+
+        // The only test touching the environment, so it doesn't race with others.
+        env::set_var("JUTELLA_API_TOKEN", "env-token");
+        env::set_var("JUTELLA_API_URL", "https://env.example/v1/");
+        env::set_var("JUTELLA_MODEL", "env-model");
+        env::set_var("JUTELLA_SYSTEM_MESSAGE", "env-message");
+
+        let args = Args::try_parse_from([
+            "jutella",
+            "--config",
+            path.to_str().unwrap(),
+            "--model",
+            "cli-model",
+        ])
+        .unwrap();
+        let config = Configuration::init(args);
+
+        for name in ["API_TOKEN", "API_URL", "MODEL", "SYSTEM_MESSAGE"] {
+            env::remove_var(format!("{ENV_PREFIX}{name}"));
+        }
+        fs::remove_dir_all(dir).unwrap();
+
+        let config = config.unwrap();
+        assert!(matches!(config.auth, Auth::Token(ref token) if token == "env-token"));
+        assert_eq!(config.api_url, "https://env.example/v1/");
+        assert_eq!(config.api_version.as_deref(), Some("file-version"));
+        assert_eq!(config.model, "cli-model");
+        assert_eq!(config.system_message.as_deref(), Some("env-message"));
+    }
+}
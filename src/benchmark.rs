@@ -0,0 +1,174 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `jutella models benchmark`: latency and throughput of the models.
+
+use crate::estimate::Prices;
+use anyhow::{ensure, Context as _};
+use colored::Colorize as _;
+use jutella::ChatClient;
+use serde::Serialize;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const BENCHMARK_PROMPT: &str = "Count from 1 to 20, separated by spaces.";
+
+/// Benchmark results of a model, appended as a JSON line to the benchmarks file.
+#[derive(Debug, Serialize)]
+struct BenchmarkRecord {
+    /// Unix timestamp of the benchmark, seconds.
+    timestamp: u64,
+    model: String,
+    runs: usize,
+    p50_ms: u128,
+    p90_ms: u128,
+    p99_ms: u128,
+    tokens_per_sec: f64,
+    /// Average cost of a request in USD, if the prices of the model are configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost_usd: Option<f64>,
+}
+
+/// Send the benchmark prompt `runs` times to each of the clients, report latency percentiles,
+/// output tokens per second and the cost per request if the model `prices` are known, and
+/// append the results to `history_file`.
+pub async fn run(
+    clients: Vec<(String, ChatClient)>,
+    runs: usize,
+    prices: &Prices,
+    history_file: &Path,
+) -> anyhow::Result<()> {
+    ensure!(runs > 0, "The number of runs must be positive");
+
+    let mut records = Vec::with_capacity(clients.len());
+
+    for (model, chat) in clients {
+        let mut latencies = Vec::with_capacity(runs);
+        let mut tokens_in = 0;
+        let mut tokens_out = 0;
+
+        for _ in 0..runs {
+            // Every run starts on a fork of the empty conversation.
+            let mut fork = chat.clone();
+            let start = Instant::now();
+            let completion = fork
+                .request_completion(String::from(BENCHMARK_PROMPT))
+                .await
+                .with_context(|| format!("Benchmark request to `{model}` failed"))?;
+            latencies.push(start.elapsed());
+            tokens_in += completion.tokens_in;
+            tokens_out += completion.tokens_out;
+        }
+
+        let total = latencies.iter().sum::<Duration>();
+        latencies.sort_unstable();
+
+        let cost_usd = prices
+            .get(&model)
+            .and_then(|price| price.cost(tokens_in, tokens_out))
+            .map(|cost| cost / runs as f64);
+        let record = BenchmarkRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            model,
+            runs,
+            p50_ms: percentile(&latencies, 50).as_millis(),
+            p90_ms: percentile(&latencies, 90).as_millis(),
+            p99_ms: percentile(&latencies, 99).as_millis(),
+            tokens_per_sec: tokens_out as f64 / total.as_secs_f64(),
+            cost_usd,
+        };
+
+        println!(
+            "{} p50 {} ms, p90 {} ms, p99 {} ms, {:.1} tokens/s{}",
+            format!("{}:", record.model).bold(),
+            record.p50_ms,
+            record.p90_ms,
+            record.p99_ms,
+            record.tokens_per_sec,
+            record
+                .cost_usd
+                .map(|cost| format!(", ${cost:.6} per request"))
+                .unwrap_or_default(),
+        );
+
+        records.push(record);
+    }
+
+    append_history(history_file, &records)?;
+    println!(
+        "{}",
+        format!("Results appended to {}", history_file.display()).cyan()
+    );
+
+    Ok(())
+}
+
+/// Append the records as JSON lines.
+fn append_history(path: &Path, records: &[BenchmarkRecord]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    for record in records {
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+    }
+
+    Ok(())
+}
+
+/// Nearest-rank percentile of sorted non-empty `values`.
+fn percentile(values: &[Duration], percent: usize) -> Duration {
+    let rank = (values.len() * percent).div_ceil(100).max(1);
+
+    values[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentile() {
+        let values = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+
+        assert_eq!(percentile(&values, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&values, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&values, 99), Duration::from_millis(10));
+        assert_eq!(
+            percentile(&[Duration::from_millis(7)], 50),
+            Duration::from_millis(7)
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `jutella config export` / `import`: portable bundle of the config and the memories.
+
+use crate::app_config::{default_memories_file, ConfigCommand};
+use anyhow::{anyhow, Context as _};
+use jutella::MemoryStore;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+};
+use toml::Table;
+
+/// Config options holding credentials.
+const SECRET_KEYS: [&str; 5] = [
+    "api_key",
+    "api_token",
+    "entra_id",
+    "proxy_password",
+    "headers",
+];
+
+/// Portable bundle of the setup.
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    /// Config file options, if there is a config file.
+    config: Option<Table>,
+    /// Remembered facts.
+    memories: Vec<String>,
+}
+
+/// Run the `config` subcommand.
+pub fn run(command: ConfigCommand, config_path: &Path) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Export { no_secrets } => export(config_path, no_secrets),
+        ConfigCommand::Import { bundle } => import(&bundle, config_path),
+    }
+}
+
+/// Print the bundle to stdout.
+fn export(config_path: &Path, no_secrets: bool) -> anyhow::Result<()> {
+    let mut config = read_config(config_path)?;
+
+    if no_secrets {
+        if let Some(config) = &mut config {
+            strip_secrets(config);
+        }
+    }
+
+    let memories = MemoryStore::load(memories_file(config.as_ref())?)?
+        .facts()
+        .to_vec();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&Bundle { config, memories })?
+    );
+
+    Ok(())
+}
+
+/// Replace the config with the one from the bundle and merge the memories.
+fn import(bundle_path: &Path, config_path: &Path) -> anyhow::Result<()> {
+    let mut bundle = String::new();
+    if bundle_path == Path::new("-") {
+        io::stdin().read_to_string(&mut bundle)?;
+    } else {
+        bundle = fs::read_to_string(bundle_path)
+            .with_context(|| anyhow!("Failed to read bundle {}", bundle_path.display()))?;
+    }
+    let Bundle { config, memories } =
+        serde_json::from_str(&bundle).context("Failed to parse bundle")?;
+
+    let current = read_config(config_path)?;
+
+    let config = match config {
+        Some(mut config) => {
+            if let Some(current) = &current {
+                keep_secrets(&mut config, current);
+                fs::copy(config_path, config_path.with_extension("toml.bak"))
+                    .context("Failed to back up the config")?;
+            }
+            if let Some(dir) = config_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(config_path, toml::to_string(&config)?)?;
+            println!("Imported config to {}.", config_path.display());
+
+            Some(config)
+        }
+        None => current,
+    };
+
+    let mut store = MemoryStore::load(memories_file(config.as_ref())?)?;
+    let mut imported = 0;
+    for fact in memories {
+        if !store.facts().contains(&fact) {
+            store.remember(fact)?;
+            imported += 1;
+        }
+    }
+    println!("Imported {imported} new remembered fact(s).");
+
+    Ok(())
+}
+
+/// Read the config file options, `None` if there is no config file.
+fn read_config(path: &Path) -> anyhow::Result<Option<Table>> {
+    match fs::read_to_string(path) {
+        Ok(config) => Ok(Some(toml::from_str(&config).with_context(|| {
+            anyhow!("Failed to parse config file {}", path.display())
+        })?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| anyhow!("Failed to read config file {}", path.display())),
+    }
+}
+
+/// Location of the memories configured in `config`.
+fn memories_file(config: Option<&Table>) -> anyhow::Result<PathBuf> {
+    match config
+        .and_then(|config| config.get("memories_file"))
+        .and_then(|path| path.as_str())
+    {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => default_memories_file(),
+    }
+}
+
+fn strip_secrets(config: &mut Table) {
+    for key in SECRET_KEYS {
+        config.remove(key);
+    }
+}
+
+/// Keep the `current` credentials if the imported `config` has none.
+fn keep_secrets(config: &mut Table, current: &Table) {
+    if SECRET_KEYS.iter().any(|key| config.contains_key(*key)) {
+        return;
+    }
+
+    for key in SECRET_KEYS {
+        if let Some(value) = current.get(key) {
+            config.insert(key.to_string(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets() {
+        let current: Table = toml::from_str("api_key = \"old\"\nmodel = \"gpt-4o\"").unwrap();
+
+        let mut exported = current.clone();
+        strip_secrets(&mut exported);
+        assert_eq!(exported, toml::from_str("model = \"gpt-4o\"").unwrap());
+
+        let mut imported: Table = toml::from_str("model = \"gpt-4o-mini\"").unwrap();
+        keep_secrets(&mut imported, &current);
+        assert_eq!(
+            imported,
+            toml::from_str("api_key = \"old\"\nmodel = \"gpt-4o-mini\"").unwrap()
+        );
+
+        let mut imported: Table = toml::from_str("api_token = \"new\"").unwrap();
+        keep_secrets(&mut imported, &current);
+        assert_eq!(imported, toml::from_str("api_token = \"new\"").unwrap());
+    }
+}
@@ -23,25 +23,141 @@
 //! Chatbot API client.
 
 use crate::chat_client::{
-    context::Context,
+    context::{Context, ContextStrategy, Exchange, Feedback, InitialContext, TokenBreakdown},
+    logit_bias::{self, LogitBias},
+    observer::{Observed, Observer, Turn},
+    ollama_api::{self, OllamaOptions},
     openai_api::{
-        chat_completions::ChatCompletionsBody,
-        client::{Auth, Error as OpenAiClientError, OpenAiClient},
-        message::{self, AssistantMessage},
+        chat_completions::{
+            ChatCompletions, ChatCompletionsBody, CompletionChoice, Modality, ReasoningEffort,
+            ResponseFormat, Temperature, TokenLogprob, TopP, Usage,
+        },
+        client::{
+            self as openai_client, Auth, Error as OpenAiClientError, HttpSettings, OpenAiClient,
+            Proxy, RetryPolicy, Tls,
+        },
+        message::{
+            self, AssistantMessage, GenericMessage, Image, Message, SystemMessage, ToolMessage,
+            UserMessage,
+        },
+        models::ModelList,
+        rate_limit::RateLimits,
     },
+    provider::Provider,
+    segments::Segment,
+    shared::SharedResources,
+    store::{self, ContextStore},
+    tools::{self, Tool, ToolCall, ToolResult},
 };
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, value::Value};
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+use tokio::sync::{broadcast, watch};
+
+const EVENTS_CAPACITY: usize = 64;
+const DEFAULT_JUDGE_INSTRUCTIONS: &str = "You are an impartial judge. Score every candidate \
+    answer to the user's question from 0 to 10 for correctness, helpfulness and clarity.";
+
+/// Callback invoked when the API rejects the credentials with HTTP 401 or 403.
+///
+/// Return new [`Auth`] to retry the request once with it, or `None` to fail the request.
+pub type AuthRefresh =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Option<Auth>> + Send>> + Send + Sync>;
+
+/// Provider of a system message evaluated for every request, e.g., to include the current
+/// time or the user locale. An empty message is omitted from the request.
+pub type SystemMessageProvider = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Callback notified of every finished exchange, e.g., to post it to a webhook. See
+/// [`ExchangeSummary`].
+pub type ExchangeHook = Arc<dyn Fn(ExchangeSummary) + Send + Sync>;
+
+/// Summary of an exchange passed to the [`ExchangeHook`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ExchangeSummary {
+    /// Model used for the request.
+    pub model: String,
+    /// User request.
+    pub request: String,
+    /// Assistant response, `None` if the request failed.
+    pub response: Option<String>,
+    /// Error message if the request failed.
+    pub error: Option<String>,
+    /// Input tokens used.
+    pub tokens_in: usize,
+    /// Output tokens used.
+    pub tokens_out: usize,
+}
 
 /// Configuration for [`ChatClient`].
-#[derive(Debug)]
 pub struct ChatClientConfig {
     /// OpenAI chat API endpoint.
     pub api_url: String,
     /// API version.
     pub api_version: Option<String>,
+    /// Fallback API URLs of the same deployment, e.g., in other Azure regions, used if the
+    /// current endpoint is unavailable. Five minutes after failing over, the next request is
+    /// sent to `api_url` first, switching back to it if it recovered.
+    pub fallback_api_urls: Vec<String>,
+    /// Retries of requests failing with HTTP 429, 5xx or transport errors. No retries if
+    /// `None`.
+    pub retry_policy: Option<RetryPolicy>,
+    /// Client-side limits of requests and prompt tokens per minute. Requests over the limits
+    /// are queued, including the ones of the clones of the client.
+    pub rate_limits: Option<RateLimits>,
+    /// Timeout of establishing connections, separate from the timeout of the whole request,
+    /// so unreachable endpoints fail fast. Only the request timeout applies if `None`.
+    ///
+    /// Not used by [`ChatClient::new_with_client`], configure the [`reqwest::Client`] instead.
+    pub connect_timeout: Option<Duration>,
+    /// Proxy of the API requests. The proxies from the `HTTP_PROXY` / `HTTPS_PROXY` /
+    /// `ALL_PROXY` environment variables are used if unset, and `NO_PROXY` is respected.
+    ///
+    /// Not used by [`ChatClient::new_with_client`], configure the [`reqwest::Client`] instead.
+    pub proxy: Option<Proxy>,
+    /// Root certificates to trust in addition to the built-in ones, and the client certificate
+    /// for mutual TLS, e.g., of a self-hosted gateway.
+    ///
+    /// Not used by [`ChatClient::new_with_client`], configure the [`reqwest::Client`] instead.
+    pub tls: Tls,
+    /// Custom headers of every request, e.g., `HTTP-Referer` and `X-Title` of OpenRouter, or
+    /// the auth headers of a gateway. The client auth headers take precedence.
+    pub headers: Vec<(String, String)>,
     /// Model.
     pub model: String,
+    /// Sampling temperature. The provider default is used if unset.
+    pub temperature: Option<Temperature>,
+    /// Nucleus sampling probability mass. The provider default is used if unset.
+    pub top_p: Option<TopP>,
+    /// Max tokens to generate per request, including the reasoning tokens.
+    pub max_completion_tokens: Option<usize>,
+    /// Penalty of the tokens by their frequency in the text so far, between -2 and 2.
+    pub frequency_penalty: Option<f32>,
+    /// Penalty of the tokens already present in the text so far, between -2 and 2.
+    pub presence_penalty: Option<f32>,
+    /// Seed for best-effort deterministic sampling. Compare [`Completion::system_fingerprint`]
+    /// of the responses to detect backend changes affecting determinism.
+    pub seed: Option<i64>,
+    /// Return the log probabilities of the response tokens in [`Completion::logprobs`].
+    pub logprobs: bool,
+    /// Number of the most likely tokens, between 0 and 20, to return with the log
+    /// probabilities at every position. Implies `logprobs`.
+    pub top_logprobs: Option<u8>,
+    /// Format of the responses, e.g., [`ResponseFormat::JsonObject`] for JSON mode.
+    /// Plain text if unset.
+    pub response_format: Option<ResponseFormat>,
+    /// Provider of the API. Requests with parameters the provider doesn't support are
+    /// rejected before being sent. See [`ChatClientConfig::for_provider`].
+    pub provider: Provider,
     /// System message to initialize the model.
     pub system_message: Option<String>,
+    /// System message evaluated for every request. See [`SystemMessageProvider`].
+    ///
+    /// Takes precedence over `system_message` and the system message of the initial context.
+    /// The provided message is not counted into the conversation history when truncating
+    /// the context.
+    pub system_message_provider: Option<SystemMessageProvider>,
     /// Min history tokens to keep in the conversation context.
     ///
     /// The context will be truncated to keep at least `min_history_tokens`, but
@@ -52,6 +168,30 @@ pub struct ChatClientConfig {
     pub min_history_tokens: Option<usize>,
     /// Max history tokens to keep in the conversation context.
     pub max_history_tokens: Option<usize>,
+    /// Strategy of discarding messages when the context is truncated.
+    pub context_strategy: ContextStrategy,
+    /// Tools the model can call. See [`ChatClient::submit_tool_results`].
+    pub tools: Vec<Tool>,
+    /// Options of the native Ollama API, used with [`Provider::Ollama`].
+    pub ollama_options: OllamaOptions,
+    /// Messages to start the conversation with, e.g., few-shot examples.
+    ///
+    /// The system message of the initial context takes precedence over `system_message`.
+    /// The initial messages are subject to context truncation like any other messages.
+    pub initial_context: Option<InitialContext>,
+    /// Request-response pairs of a conversation to resume, e.g., stored by the application,
+    /// oldest first. They follow the initial context and are truncated on construction to fit
+    /// the history limits.
+    pub initial_history: Vec<(String, String)>,
+    /// Durable storage of the conversation history. The stored exchanges follow the initial
+    /// context and history, and new exchanges are appended to the store as they happen. Forks of
+    /// the client created with `clone()` don't persist their exchanges.
+    pub context_store: Option<Arc<dyn ContextStore>>,
+    /// Credentials refresh callback for short-lived tokens. See [`AuthRefresh`].
+    pub on_auth_expired: Option<AuthRefresh>,
+    /// Callback notified once an exchange completes or fails, including the ones of the forks
+    /// of the client. Exchanges waiting for tool results are not reported until completed.
+    pub on_exchange: Option<ExchangeHook>,
 }
 
 impl Default for ChatClientConfig {
@@ -59,23 +199,326 @@ impl Default for ChatClientConfig {
         Self {
             api_url: String::from("https://api.openai.com/v1/"),
             api_version: None,
+            fallback_api_urls: Vec::new(),
+            retry_policy: None,
+            rate_limits: None,
+            connect_timeout: None,
+            proxy: None,
+            tls: Tls::default(),
+            headers: Vec::new(),
             model: String::from("gpt-4o-mini"),
+            temperature: None,
+            top_p: None,
+            max_completion_tokens: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            seed: None,
+            logprobs: false,
+            top_logprobs: None,
+            response_format: None,
+            provider: Provider::default(),
             system_message: None,
+            system_message_provider: None,
             min_history_tokens: None,
             max_history_tokens: None,
+            context_strategy: ContextStrategy::default(),
+            tools: Vec::new(),
+            ollama_options: OllamaOptions::default(),
+            initial_context: None,
+            initial_history: Vec::new(),
+            context_store: None,
+            on_auth_expired: None,
+            on_exchange: None,
+        }
+    }
+}
+
+impl ChatClientConfig {
+    /// Default configuration for the provider: its API URL and an inexpensive general
+    /// purpose model.
+    pub fn for_provider(provider: Provider) -> Self {
+        Self {
+            api_url: String::from(provider.api_url()),
+            model: String::from(provider.default_model()),
+            provider,
+            ..Default::default()
         }
     }
 }
 
+impl fmt::Debug for ChatClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChatClientConfig")
+            .field("api_url", &self.api_url)
+            .field("api_version", &self.api_version)
+            .field("fallback_api_urls", &self.fallback_api_urls)
+            .field("retry_policy", &self.retry_policy)
+            .field("rate_limits", &self.rate_limits)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("proxy", &self.proxy)
+            .field("tls", &self.tls)
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("model", &self.model)
+            .field("temperature", &self.temperature)
+            .field("top_p", &self.top_p)
+            .field("max_completion_tokens", &self.max_completion_tokens)
+            .field("frequency_penalty", &self.frequency_penalty)
+            .field("presence_penalty", &self.presence_penalty)
+            .field("seed", &self.seed)
+            .field("logprobs", &self.logprobs)
+            .field("top_logprobs", &self.top_logprobs)
+            .field("response_format", &self.response_format)
+            .field("provider", &self.provider)
+            .field("system_message", &self.system_message)
+            .field(
+                "system_message_provider",
+                &self.system_message_provider.is_some(),
+            )
+            .field("min_history_tokens", &self.min_history_tokens)
+            .field("max_history_tokens", &self.max_history_tokens)
+            .field("context_strategy", &self.context_strategy)
+            .field("tools", &self.tools)
+            .field("ollama_options", &self.ollama_options)
+            .field("initial_context", &self.initial_context)
+            .field("initial_history", &self.initial_history)
+            .field("context_store", &self.context_store.is_some())
+            .field("on_auth_expired", &self.on_auth_expired.is_some())
+            .field("on_exchange", &self.on_exchange.is_some())
+            .finish()
+    }
+}
+
 /// Generated completion.
 #[derive(Debug)]
 pub struct Completion {
-    /// Generated response.
+    /// Generated response. Empty if the model refused the request, the request or the response
+    /// was filtered, or the model requested tool calls.
     pub response: String,
+    /// Tool calls requested by the model. Submit the results with
+    /// [`ChatClient::submit_tool_results`] to get the response.
+    pub tool_calls: Vec<ToolCall>,
     /// Input tokens used.
     pub tokens_in: usize,
     /// Output tokens used.
     pub tokens_out: usize,
+    /// Refusal and content filtering outcome.
+    pub safety: SafetyInfo,
+    /// Images generated by the model, if requested with [`RequestOptions::modalities`].
+    /// Images are not kept in the context.
+    pub images: Vec<Image>,
+    /// Fingerprint of the backend configuration that generated the completion, if returned by
+    /// the provider. Changes of the fingerprint may break the determinism of seeded requests.
+    pub system_fingerprint: Option<String>,
+    /// Log probabilities of the response tokens, if requested with
+    /// [`ChatClientConfig::logprobs`] or [`RequestOptions::logprobs`].
+    pub logprobs: Vec<TokenLogprob>,
+}
+
+impl Completion {
+    /// The response lexed into text, code, math and table segments.
+    pub fn segments(&self) -> Vec<Segment<'_>> {
+        Segment::parse(&self.response)
+    }
+}
+
+/// Overrides of the request parameters for a single request, see
+/// [`ChatClient::request_completion_with`]. Unset parameters keep their defaults.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RequestOptions {
+    /// Sampling temperature.
+    pub temperature: Option<Temperature>,
+    /// Nucleus sampling probability mass.
+    pub top_p: Option<TopP>,
+    /// Max tokens to generate, including the reasoning tokens.
+    pub max_completion_tokens: Option<usize>,
+    /// Penalty of the tokens by their frequency in the text so far, between -2 and 2.
+    pub frequency_penalty: Option<f32>,
+    /// Penalty of the tokens already present in the text so far, between -2 and 2.
+    pub presence_penalty: Option<f32>,
+    /// Stop sequences replacing the ones set with [`ChatClient::set_stop_sequences`].
+    pub stop: Option<Vec<String>>,
+    /// Reasoning effort of reasoning models.
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Seed for best-effort deterministic sampling.
+    pub seed: Option<i64>,
+    /// Whether to return the log probabilities of the response tokens.
+    pub logprobs: Option<bool>,
+    /// Number of the most likely tokens, between 0 and 20, to return with the log
+    /// probabilities at every position. Implies `logprobs`.
+    pub top_logprobs: Option<u8>,
+    /// Format of the response.
+    pub response_format: Option<ResponseFormat>,
+    /// Output types to generate, e.g., `[Modality::Image, Modality::Text]` to request images
+    /// from OpenRouter image models. See [`Completion::images`].
+    pub modalities: Option<Vec<Modality>>,
+    /// Biases of the tokens of texts, converted to token IDs with the tokenizer of the model.
+    /// Fails with [`Error::LogitBias`] if the tokenizer of the model is not known.
+    pub logit_bias: Option<LogitBias>,
+}
+
+impl RequestOptions {
+    fn apply(self, body: &mut ChatCompletionsBody) {
+        let RequestOptions {
+            temperature,
+            top_p,
+            max_completion_tokens,
+            frequency_penalty,
+            presence_penalty,
+            stop,
+            reasoning_effort,
+            seed,
+            logprobs,
+            top_logprobs,
+            response_format,
+            modalities,
+            // Converted to token IDs by the client, which knows the tokenizer.
+            logit_bias: _,
+        } = self;
+
+        body.temperature = temperature.map(Temperature::get).or(body.temperature);
+        body.top_p = top_p.map(TopP::get).or(body.top_p);
+        body.max_completion_tokens = max_completion_tokens.or(body.max_completion_tokens);
+        body.frequency_penalty = frequency_penalty.or(body.frequency_penalty);
+        body.presence_penalty = presence_penalty.or(body.presence_penalty);
+        body.reasoning_effort = reasoning_effort.or(body.reasoning_effort);
+        body.seed = seed.or(body.seed);
+        body.logprobs = logprobs.or(body.logprobs);
+        body.top_logprobs = top_logprobs.or(body.top_logprobs);
+        body.response_format = response_format.or(body.response_format.take());
+        if body.top_logprobs.is_some() {
+            body.logprobs = Some(true);
+        }
+        if let Some(stop) = stop {
+            body.stop = stop;
+        }
+        if let Some(modalities) = modalities {
+            body.modalities = modalities;
+        }
+    }
+}
+
+/// Refusal and content filtering information of a completion.
+#[derive(Debug, Default, Clone)]
+pub struct SafetyInfo {
+    /// Refusal message if the model refused the request.
+    pub refusal: Option<String>,
+    /// The response was omitted or truncated by the provider content filter
+    /// (`finish_reason == "content_filter"`).
+    pub content_filtered: bool,
+    /// Azure content filter annotations of the response.
+    pub content_filter_results: Option<Value>,
+    /// Azure content filter annotations of the prompt.
+    pub prompt_filter_results: Option<Value>,
+    /// OpenRouter moderation metadata if the request was rejected as flagged: `reasons`,
+    /// `flagged_input`, `provider_name` and `model_slug`.
+    pub moderation: Option<Value>,
+}
+
+impl SafetyInfo {
+    /// Whether the model refused the request, or the request or the response was filtered.
+    pub fn is_flagged(&self) -> bool {
+        self.refusal.is_some() || self.content_filtered || self.moderation.is_some()
+    }
+}
+
+/// Request lifecycle event. Subscribe with [`ChatClient::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A request to the API is being sent.
+    RequestStarted,
+    /// The API started responding: the response headers were received.
+    ///
+    /// Can be emitted more than once per request if the request is retried.
+    ResponseStarted,
+    /// The request finished, successfully or not.
+    RequestFinished {
+        /// Whether the request succeeded.
+        success: bool,
+    },
+    /// Something non-fatal worth notifying the user about happened.
+    Warning(Warning),
+}
+
+/// Non-fatal issue reported with [`Event::Warning`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// The response contains no token usage, the tokens used are reported as zero.
+    UsageMissing,
+    /// Old request-response pairs were discarded to keep the context within the limits.
+    ContextTruncated {
+        /// Number of pairs discarded.
+        discarded: usize,
+    },
+    /// The request was retried with refreshed credentials.
+    AuthRetried,
+    /// The exchange couldn't be saved to the context store.
+    NotPersisted,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UsageMissing => write!(f, "The response contains no token usage"),
+            Warning::ContextTruncated { discarded } => write!(
+                f,
+                "Discarded {discarded} old request-response pair(s) from the context"
+            ),
+            Warning::AuthRetried => write!(f, "Retried the request with refreshed credentials"),
+            Warning::NotPersisted => write!(f, "Failed to save the exchange to the context store"),
+        }
+    }
+}
+
+/// Judge model configuration for [`ChatClient::best_of`].
+#[derive(Debug, Clone)]
+pub struct JudgeConfig {
+    /// Model used to score the candidates, usually a stronger one than the chat model.
+    pub model: String,
+    /// Model generating the candidates, e.g., a cheaper one. The chat model is used if `None`.
+    pub candidate_model: Option<String>,
+    /// Scoring instructions for the judge. Default: score for correctness, helpfulness
+    /// and clarity.
+    pub instructions: Option<String>,
+}
+
+/// Candidate selected by [`ChatClient::best_of`].
+#[derive(Debug)]
+pub struct BestOf {
+    /// Winning response.
+    pub response: String,
+    /// Index of the winning response in `candidates`.
+    pub index: usize,
+    /// All generated candidates.
+    pub candidates: Vec<String>,
+    /// Judge scores of the candidates, in the same order.
+    pub scores: Vec<f64>,
+    /// Refusal and content filtering information of the winning response. A flagged response
+    /// doesn't extend the chat context.
+    pub safety: SafetyInfo,
+    /// Input tokens used, including the judge request.
+    pub tokens_in: usize,
+    /// Output tokens used, including the judge response.
+    pub tokens_out: usize,
+}
+
+/// Request-response pair in the conversation, as reported by [`ChatClient::eviction_preview`].
+#[derive(Debug)]
+pub struct TurnPreview<'a> {
+    /// User request.
+    pub request: &'a str,
+    /// Assistant response.
+    pub response: &'a str,
+    /// Tokens in the request and response. `None` if the context is not truncated.
+    pub tokens: Option<usize>,
+    /// Whether the pair will be discarded from the context after the next exchange.
+    pub evicted: bool,
 }
 
 /// Errors during interaction with a chatbot.
@@ -96,19 +539,154 @@ pub enum Error {
     /// The completion response message contains no `content`.
     #[error("Assistant message contains no `content`")]
     NoContent,
-    /// Model refused the request.
+    /// Model refused the request. Only returned by [`ChatClient::ask`], see
+    /// [`Completion::safety`] and [`BestOf::safety`] otherwise.
     #[error("Model refused the request: \"{0}\"")]
     Refusal(String),
+    /// The response was omitted by the content filter. Only returned by [`ChatClient::ask`],
+    /// see [`Completion::safety`] otherwise.
+    #[error("Response omitted by the content filter")]
+    ContentFiltered,
+    /// The request was rejected by the provider moderation. Only returned by
+    /// [`ChatClient::ask`], see [`Completion::safety`] otherwise.
+    #[error("Request flagged by moderation: {0}")]
+    Moderated(String),
     /// Tokenizer initialization error.
     #[error("Failed to initialize tokenizer: {0}")]
     TokenizerInit(String),
+    /// [`ChatClient::best_of`] was asked for zero candidates.
+    #[error("At least one candidate must be requested")]
+    NoCandidatesRequested,
+    /// The judge response could not be interpreted as candidate scores.
+    #[error("Invalid judge response: {0}")]
+    InvalidJudgement(String),
+    /// The response doesn't match the requested type. Only returned by `ChatClient::ask_as`.
+    #[error("Response doesn't match the requested structure: {0}")]
+    InvalidStructuredOutput(String),
+    /// The model requested tool calls. Only returned by [`ChatClient::ask`], use
+    /// [`ChatClient::request_completion`] to handle tool calls.
+    #[error("Model requested tool calls")]
+    ToolCallsRequested,
+    /// The `tool_calls` of the assistant message could not be parsed.
+    #[error("Invalid tool calls: {0}")]
+    InvalidToolCalls(String),
+    /// Tool results were submitted, but the model didn't request any tool calls.
+    #[error("No tool calls pending")]
+    NoPendingToolCalls,
+    /// There is no exchange to attach the feedback to. Only returned by
+    /// [`ChatClient::set_feedback`].
+    #[error("No exchange in the conversation")]
+    NoExchange,
+    /// The model is not available on the endpoint. Only returned by
+    /// [`ChatClient::validate_model`].
+    #[error(
+        "Model `{model}` is not available{}",
+        .suggestion.as_ref().map(|s| format!(", did you mean `{s}`?")).unwrap_or_default()
+    )]
+    UnknownModel {
+        /// Requested model.
+        model: String,
+        /// Available model with the closest name, if the name looks like a typo.
+        suggestion: Option<String>,
+    },
+    /// The request was cancelled with an [`AbortHandle`].
+    #[error("Request cancelled")]
+    Cancelled,
+    /// Context store error.
+    #[error("Context store error: {0}")]
+    ContextStore(#[from] store::Error),
+    /// Logit bias could not be converted to token IDs.
+    #[error("Invalid logit bias: {0}")]
+    LogitBias(#[from] logit_bias::Error),
+    /// The provider doesn't support a parameter of the request.
+    #[error("Parameter `{parameter}` is not supported by {provider:?}")]
+    UnsupportedParameter {
+        /// API provider.
+        provider: Provider,
+        /// Parameter name.
+        parameter: &'static str,
+    },
+}
+
+/// Context store of a client, not inherited by its forks.
+#[derive(Default)]
+struct Persistence(Option<Arc<dyn ContextStore>>);
+
+impl Clone for Persistence {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+/// Exchange waiting for the tool results.
+#[derive(Clone)]
+struct ToolTurn {
+    /// User request.
+    request: String,
+    /// Assistant tool call requests and tool results following the user request.
+    messages: Vec<Message>,
 }
 
 /// Chatbot API client.
+///
+/// Cloning the client forks the conversation: the clone starts with the same context,
+/// but further exchanges are not shared between the two.
+#[derive(Clone)]
 pub struct ChatClient {
     client: OpenAiClient,
     model: String,
+    provider: Provider,
     context: Context,
+    context_store: Persistence,
+    observed: Observed,
+    tools: Vec<Value>,
+    tool_turn: Option<ToolTurn>,
+    /// Tokenizer to estimate prompt tokens for the tokens per minute limit.
+    rate_limit_tokenizer: Option<Arc<tiktoken_rs::CoreBPE>>,
+    ollama_options: OllamaOptions,
+    system_message_provider: Option<SystemMessageProvider>,
+    /// Request parameters of [`ChatClientConfig`].
+    defaults: RequestOptions,
+    stop: Vec<String>,
+    on_auth_expired: Option<AuthRefresh>,
+    on_exchange: Option<ExchangeHook>,
+    events: broadcast::Sender<Event>,
+    abort: Abort,
+}
+
+/// Handle cancelling the requests of a [`ChatClient`] in flight, obtained with
+/// [`ChatClient::abort_handle`].
+///
+/// A cancelled request fails with [`Error::Cancelled`] and doesn't change the conversation
+/// context: the response is either committed in full or not at all. Pending tool calls stay
+/// pending if submitting their results is cancelled.
+#[derive(Clone)]
+pub struct AbortHandle {
+    generation: Arc<watch::Sender<u64>>,
+}
+
+impl AbortHandle {
+    /// Cancel the requests in flight. Requests started afterwards are not affected.
+    pub fn abort(&self) {
+        self.generation.send_modify(|generation| *generation += 1);
+    }
+}
+
+/// Client side of the abort handles. Forks of the client get their own.
+struct Abort(AbortHandle);
+
+impl Default for Abort {
+    fn default() -> Self {
+        Self(AbortHandle {
+            generation: Arc::new(watch::channel(0).0),
+        })
+    }
+}
+
+impl Clone for Abort {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
 }
 
 impl ChatClient {
@@ -117,19 +695,104 @@ impl ChatClient {
         let ChatClientConfig {
             api_url,
             api_version,
+            fallback_api_urls,
+            retry_policy,
+            rate_limits,
+            connect_timeout,
+            proxy,
+            tls,
+            headers,
             model,
+            temperature,
+            top_p,
+            max_completion_tokens,
+            frequency_penalty,
+            presence_penalty,
+            seed,
+            logprobs,
+            top_logprobs,
+            response_format,
+            provider,
             system_message,
+            system_message_provider,
             min_history_tokens,
             max_history_tokens,
+            context_strategy,
+            tools,
+            ollama_options,
+            initial_context,
+            initial_history,
+            context_store,
+            on_auth_expired,
+            on_exchange,
         } = config;
 
         let api_url = ensure_trailing_slash(api_url);
-        let context = create_context(system_message, min_history_tokens, max_history_tokens)?;
+        let fallback_api_urls = fallback_api_urls
+            .into_iter()
+            .map(ensure_trailing_slash)
+            .collect::<Vec<_>>();
+        for url in &fallback_api_urls {
+            openai_client::ensure_tls_support(url)?;
+        }
+        let stored = context_store
+            .as_ref()
+            .map(|store| store.load())
+            .transpose()?
+            .unwrap_or_default();
+        let context = create_context(
+            system_message,
+            initial_context,
+            initial_history.into_iter().chain(stored).collect(),
+            system_message_provider.is_some(),
+            min_history_tokens,
+            max_history_tokens,
+            context_strategy,
+        )?;
 
         Ok(Self {
-            client: OpenAiClient::new(auth, api_url, api_version)?,
+            client: OpenAiClient::new(
+                auth,
+                api_url,
+                api_version,
+                HttpSettings {
+                    connect_timeout,
+                    proxy,
+                    tls,
+                },
+            )?
+            .with_headers(headers)?
+            .with_fallback_urls(fallback_api_urls)
+            .with_endpoint(provider.chat_endpoint())
+            .with_retry_policy(retry_policy)
+            .with_rate_limits(rate_limits),
             model,
+            provider,
+            observed: Observed::new(context.exchanges()),
             context,
+            context_store: Persistence(context_store),
+            tools: tools.iter().map(Tool::to_json).collect(),
+            tool_turn: None,
+            rate_limit_tokenizer: rate_limit_tokenizer(rate_limits)?,
+            ollama_options,
+            system_message_provider,
+            defaults: RequestOptions {
+                temperature,
+                top_p,
+                max_completion_tokens,
+                frequency_penalty,
+                presence_penalty,
+                seed,
+                logprobs: logprobs.then_some(true),
+                top_logprobs,
+                response_format,
+                ..Default::default()
+            },
+            stop: Vec::new(),
+            on_auth_expired,
+            on_exchange,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            abort: Abort::default(),
         })
     }
 
@@ -144,67 +807,1077 @@ impl ChatClient {
         let ChatClientConfig {
             api_url,
             api_version,
+            fallback_api_urls,
+            retry_policy,
+            rate_limits,
+            connect_timeout: _,
+            proxy: _,
+            tls: _,
+            headers,
             model,
+            temperature,
+            top_p,
+            max_completion_tokens,
+            frequency_penalty,
+            presence_penalty,
+            seed,
+            logprobs,
+            top_logprobs,
+            response_format,
+            provider,
             system_message,
+            system_message_provider,
             min_history_tokens,
             max_history_tokens,
+            context_strategy,
+            tools,
+            ollama_options,
+            initial_context,
+            initial_history,
+            context_store,
+            on_auth_expired,
+            on_exchange,
         } = config;
 
         let api_url = ensure_trailing_slash(api_url);
-        let context = create_context(system_message, min_history_tokens, max_history_tokens)?;
+        let fallback_api_urls = fallback_api_urls
+            .into_iter()
+            .map(ensure_trailing_slash)
+            .collect();
+        let stored = context_store
+            .as_ref()
+            .map(|store| store.load())
+            .transpose()?
+            .unwrap_or_default();
+        let context = create_context(
+            system_message,
+            initial_context,
+            initial_history.into_iter().chain(stored).collect(),
+            system_message_provider.is_some(),
+            min_history_tokens,
+            max_history_tokens,
+            context_strategy,
+        )?;
 
         Ok(Self {
-            client: OpenAiClient::new_with_client(client, api_url, api_version),
+            client: OpenAiClient::new_with_client(client, api_url, api_version)
+                .with_headers(headers)?
+                .with_fallback_urls(fallback_api_urls)
+                .with_endpoint(provider.chat_endpoint())
+                .with_retry_policy(retry_policy)
+                .with_rate_limits(rate_limits),
             model,
+            provider,
+            observed: Observed::new(context.exchanges()),
             context,
+            context_store: Persistence(context_store),
+            tools: tools.iter().map(Tool::to_json).collect(),
+            tool_turn: None,
+            rate_limit_tokenizer: rate_limit_tokenizer(rate_limits)?,
+            ollama_options,
+            system_message_provider,
+            defaults: RequestOptions {
+                temperature,
+                top_p,
+                max_completion_tokens,
+                frequency_penalty,
+                presence_penalty,
+                seed,
+                logprobs: logprobs.then_some(true),
+                top_logprobs,
+                response_format,
+                ..Default::default()
+            },
+            stop: Vec::new(),
+            on_auth_expired,
+            on_exchange,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            abort: Abort::default(),
         })
     }
 
     /// Ask a new question, extending the chat context after a successful respone.
+    ///
+    /// Refusals and filtered responses are reported as errors.
     pub async fn ask(&mut self, request: String) -> Result<String, Error> {
-        self.request_completion(request).await.map(|c| c.response)
+        let completion = self.request_completion(request).await?;
+
+        Self::response_or_error(completion)
+    }
+
+    /// Ask a new question like [`ChatClient::ask`], overriding the request parameters with
+    /// `options` for this request only.
+    pub async fn ask_with(
+        &mut self,
+        request: String,
+        options: RequestOptions,
+    ) -> Result<String, Error> {
+        let completion = self.request_completion_with(request, options).await?;
+
+        Self::response_or_error(completion)
+    }
+
+    /// Ask a new question and parse the response into `T`, extending the chat context after
+    /// a successful response.
+    ///
+    /// The model is requested to respond in JSON following the schema of `T` generated by
+    /// `schemars`, see [`ResponseFormat::for_type`]. Refusals and filtered responses are
+    /// reported as errors.
+    #[cfg(feature = "schemars")]
+    pub async fn ask_as<T>(&mut self, request: String) -> Result<T, Error>
+    where
+        T: schemars::JsonSchema + serde::de::DeserializeOwned,
+    {
+        self.tool_turn = None;
+        let mut body = self.body(request.clone());
+        body.response_format = Some(ResponseFormat::for_type::<T>());
+
+        let completion = self.complete_turn(request, Vec::new(), body).await?;
+        let response = Self::response_or_error(completion)?;
+
+        serde_json::from_str(&response).map_err(|e| Error::InvalidStructuredOutput(e.to_string()))
+    }
+
+    /// Response of the completion, or an error if the model refused the request, the response
+    /// was filtered or the model requested tool calls.
+    fn response_or_error(completion: Completion) -> Result<String, Error> {
+        let Completion {
+            response,
+            tool_calls,
+            safety,
+            ..
+        } = completion;
+
+        if !tool_calls.is_empty() {
+            return Err(Error::ToolCallsRequested);
+        }
+
+        match safety {
+            SafetyInfo {
+                refusal: Some(refusal),
+                ..
+            } => Err(Error::Refusal(refusal)),
+            SafetyInfo {
+                moderation: Some(moderation),
+                ..
+            } => Err(Error::Moderated(
+                moderation["reasons"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+            SafetyInfo {
+                content_filtered: true,
+                ..
+            } if response.is_empty() => Err(Error::ContentFiltered),
+            _ => Ok(response),
+        }
     }
 
     /// Request completion, extending the chat context after a successful respone.
+    ///
+    /// Refused and filtered completions are returned with [`Completion::safety`] set,
+    /// but don't extend the chat context.
+    ///
+    /// If the model requests tool calls, they are returned in [`Completion::tool_calls`], and
+    /// the exchange is completed with [`ChatClient::submit_tool_results`]. A new request
+    /// abandons the pending tool calls.
     pub async fn request_completion(&mut self, request: String) -> Result<Completion, Error> {
-        let mut completion = self
-            .client
-            .chat_completions(Self::body(
-                self.model.clone(),
-                &self.context,
-                request.clone(),
-            ))
-            .await?;
+        self.request_completion_with(request, RequestOptions::default())
+            .await
+    }
+
+    /// Request completion like [`ChatClient::request_completion`], overriding the request
+    /// parameters with `options` for this request only.
+    pub async fn request_completion_with(
+        &mut self,
+        request: String,
+        mut options: RequestOptions,
+    ) -> Result<Completion, Error> {
+        self.tool_turn = None;
+        let mut body = self.body(request.clone());
+        if let Some(logit_bias) = options.logit_bias.take() {
+            body.logit_bias = logit_bias.for_model(&body.model)?;
+        }
+        options.apply(&mut body);
+
+        self.complete_turn(request, Vec::new(), body).await
+    }
+
+    /// Pass the results of the tool calls requested in the last completion back to the model,
+    /// continuing the exchange.
+    ///
+    /// The model may request more tool calls. Once the model responds, the request and
+    /// the response extend the chat context; the tool calls and results are not kept.
+    pub async fn submit_tool_results(
+        &mut self,
+        results: Vec<ToolResult>,
+    ) -> Result<Completion, Error> {
+        let ToolTurn {
+            request,
+            mut messages,
+        } = self.tool_turn.clone().ok_or(Error::NoPendingToolCalls)?;
+
+        messages.extend(results.into_iter().map(|result| {
+            Message::from(ToolMessage {
+                content: result.content,
+                tool_call_id: result.call_id,
+            })
+        }));
+
+        let mut body = self.body(request.clone());
+        body.messages
+            .extend(messages.iter().cloned().map(Into::into));
+
+        self.complete_turn(request, messages, body).await
+    }
+
+    /// Request completion following the `request` and the tool `messages`, and either extend
+    /// the context with the response or keep the turn waiting for the tool results.
+    ///
+    /// The exchange hook is notified unless the turn waits for the tool results or is ephemeral.
+    async fn complete_turn(
+        &mut self,
+        request: String,
+        messages: Vec<Message>,
+        body: ChatCompletionsBody,
+    ) -> Result<Completion, Error> {
+        let notify = self.notifies_exchanges();
+        let model = body.model.clone();
+        let result = self
+            .try_complete_turn(request.clone(), messages, body)
+            .await;
+
+        match &result {
+            Ok(completion) if !completion.tool_calls.is_empty() => {}
+            _ if !notify => {}
+            Ok(completion) => self.notify_exchange(
+                model,
+                request,
+                Ok((
+                    &completion.response,
+                    completion.tokens_in,
+                    completion.tokens_out,
+                )),
+            ),
+            Err(error) => self.notify_exchange(model, request, Err(error)),
+        }
+
+        result
+    }
+
+    /// Whether the next exchange is reported to the exchange hook: there is a hook and
+    /// the exchange is not ephemeral.
+    fn notifies_exchanges(&self) -> bool {
+        self.on_exchange.is_some() && self.ephemeral().is_none()
+    }
+
+    /// Report the response and the tokens used, or the error, to the exchange hook.
+    fn notify_exchange(
+        &self,
+        model: String,
+        request: String,
+        result: Result<(&str, usize, usize), &Error>,
+    ) {
+        let Some(on_exchange) = &self.on_exchange else {
+            return;
+        };
+
+        let (response, error, tokens_in, tokens_out) = match result {
+            Ok((response, tokens_in, tokens_out)) => {
+                (Some(response.to_string()), None, tokens_in, tokens_out)
+            }
+            Err(error) => (None, Some(error.to_string()), 0, 0),
+        };
+        on_exchange(ExchangeSummary {
+            model,
+            request,
+            response,
+            error,
+            tokens_in,
+            tokens_out,
+        });
+    }
+
+    async fn try_complete_turn(
+        &mut self,
+        request: String,
+        mut messages: Vec<Message>,
+        body: ChatCompletionsBody,
+    ) -> Result<Completion, Error> {
+        let dynamic_system_tokens = self.dynamic_system_tokens(&body);
+        let mut completion = match self.chat_completions(body).await {
+            Err(Error::OpenAiClient(error)) if error.moderation().is_some() => {
+                self.tool_turn = None;
 
-        let choice = completion.choices.pop().ok_or(Error::NoChoices)?;
+                return Ok(Completion {
+                    response: String::new(),
+                    tool_calls: Vec::new(),
+                    tokens_in: 0,
+                    tokens_out: 0,
+                    safety: SafetyInfo {
+                        moderation: error.moderation().cloned(),
+                        ..Default::default()
+                    },
+                    images: Vec::new(),
+                    system_fingerprint: None,
+                    logprobs: Vec::new(),
+                });
+            }
+            result => result?,
+        };
+
+        let mut choice = completion.choices.pop().ok_or(Error::NoChoices)?;
+        let content_filtered = choice.finish_reason == "content_filter";
+        let images = choice.message.take_images();
+        let logprobs = choice
+            .logprobs
+            .and_then(|logprobs| logprobs.content)
+            .unwrap_or_default();
         let assistant_message = AssistantMessage::try_from(choice.message)?;
-        let response = assistant_message.content.ok_or(
-            assistant_message
-                .refusal
-                .map_or(Error::NoContent, Error::Refusal),
-        )?;
+        let tool_calls = tools::parse_tool_calls(assistant_message.tool_calls.as_ref())
+            .map_err(Error::InvalidToolCalls)?;
+
+        if !tool_calls.is_empty() {
+            messages.push(assistant_message.clone().into());
+        }
+
+        let safety = SafetyInfo {
+            refusal: assistant_message.refusal,
+            content_filtered,
+            content_filter_results: choice.content_filter_results,
+            prompt_filter_results: completion.prompt_filter_results,
+            moderation: None,
+        };
+
+        let response = match assistant_message.content {
+            Some(content) => content,
+            None if safety.is_flagged() || !tool_calls.is_empty() || !images.is_empty() => {
+                String::new()
+            }
+            None => return Err(Error::NoContent),
+        };
 
         // TODO: we likely need to count tokens used in case of errors as well.
 
-        self.context.push(request, response.clone());
+        if !tool_calls.is_empty() {
+            self.tool_turn = Some(ToolTurn { request, messages });
+        } else {
+            self.tool_turn = None;
+
+            if !safety.is_flagged() {
+                // The prompt of a tool turn also contains the tool calls and results, so its
+                // usage doesn't match the context.
+                let usage = completion
+                    .usage
+                    .as_ref()
+                    .filter(|_| messages.is_empty())
+                    .zip(dynamic_system_tokens)
+                    .map(|(usage, system_tokens)| {
+                        let reasoning_tokens = usage
+                            .completion_tokens_details
+                            .as_ref()
+                            .and_then(|details| details.get("reasoning_tokens"))
+                            .and_then(Value::as_u64)
+                            .unwrap_or_default()
+                            as usize;
+
+                        (
+                            usage.prompt_tokens.saturating_sub(system_tokens),
+                            usage.completion_tokens.saturating_sub(reasoning_tokens),
+                        )
+                    });
+                let exchange = self
+                    .context
+                    .exchange(request, response.clone())
+                    .with_completion(completion.id.clone(), completion.model.clone());
+                self.push_to_context(exchange, usage);
+            }
+        }
+
+        let (tokens_in, tokens_out) = self.token_usage(completion.usage);
 
         Ok(Completion {
             response,
-            tokens_in: completion.usage.prompt_tokens,
-            tokens_out: completion.usage.completion_tokens,
+            tool_calls,
+            tokens_in,
+            tokens_out,
+            safety,
+            images,
+            system_fingerprint: Some(completion.system_fingerprint)
+                .filter(|fingerprint| !fingerprint.is_empty()),
+            logprobs,
+        })
+    }
+
+    /// Generate `n` candidate responses and let a judge model pick the best one.
+    ///
+    /// The candidates are generated in a single request with
+    /// [`JudgeConfig::candidate_model`], or the chat model. The judge only sees the request and
+    /// the candidates, not the conversation history. Unless flagged, the winning response
+    /// extends the chat context like the response of [`ChatClient::request_completion`], and is
+    /// reported to the exchange hook.
+    pub async fn best_of(
+        &mut self,
+        request: String,
+        n: usize,
+        judge: JudgeConfig,
+    ) -> Result<BestOf, Error> {
+        if n == 0 {
+            return Err(Error::NoCandidatesRequested);
+        }
+
+        self.tool_turn = None;
+        let notify = self.notifies_exchanges();
+        let model = judge
+            .candidate_model
+            .clone()
+            .unwrap_or_else(|| self.model.clone());
+        let result = self
+            .try_best_of(request.clone(), n, model.clone(), judge)
+            .await;
+
+        if notify {
+            let result = result
+                .as_ref()
+                .map(|best| (best.response.as_str(), best.tokens_in, best.tokens_out));
+            self.notify_exchange(model, request, result);
+        }
+
+        result
+    }
+
+    async fn try_best_of(
+        &mut self,
+        request: String,
+        n: usize,
+        model: String,
+        judge: JudgeConfig,
+    ) -> Result<BestOf, Error> {
+        let mut body = self.body(request.clone());
+        body.model = model;
+        body.completion_choices = Some(n);
+
+        let mut completion = self.chat_completions(body).await?;
+        completion.choices.sort_by_key(|choice| choice.index);
+
+        let (candidates, safety): (Vec<_>, Vec<_>) = completion
+            .choices
+            .into_iter()
+            .map(|choice| candidate(choice, completion.prompt_filter_results.clone()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+
+        if candidates.is_empty() {
+            return Err(Error::NoChoices);
+        }
+
+        let mut judgement = self
+            .chat_completions(Self::judge_body(judge, &request, &candidates))
+            .await?;
+
+        let choice = judgement.choices.pop().ok_or(Error::NoChoices)?;
+        let scores = serde_json::from_str::<Scores>(&response_content(choice)?)
+            .map_err(|e| Error::InvalidJudgement(e.to_string()))?
+            .scores;
+
+        if scores.len() != candidates.len() {
+            return Err(Error::InvalidJudgement(format!(
+                "expected {} scores, got {}",
+                candidates.len(),
+                scores.len(),
+            )));
+        }
+
+        let index = scores
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .expect("there is at least one candidate");
+        let response = candidates[index].clone();
+        let safety = safety.into_iter().nth(index).unwrap_or_default();
+
+        if !safety.is_flagged() {
+            let exchange = self
+                .context
+                .exchange(request, response.clone())
+                .with_completion(completion.id, completion.model);
+            self.push_to_context(exchange, None);
+        }
+
+        let (candidates_in, candidates_out) = self.token_usage(completion.usage);
+        let (judgement_in, judgement_out) = self.token_usage(judgement.usage);
+
+        Ok(BestOf {
+            response,
+            index,
+            candidates,
+            scores,
+            safety,
+            tokens_in: candidates_in + judgement_in,
+            tokens_out: candidates_out + judgement_out,
         })
     }
 
+    /// Subscribe to request lifecycle events and warnings, e.g., to show typing indicators.
+    ///
+    /// Forks of the client created with `clone()` report to the same subscribers.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Handle to cancel the requests in flight, e.g., from another task on user input.
+    ///
+    /// Forks of the client created with `clone()` are not cancelled with the handle.
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.0.clone()
+    }
+
+    /// Read-only handle to the conversation context and the token usage, notified of
+    /// the turns added to the context.
+    ///
+    /// Unlike [`ChatClient::subscribe`], the forks of the client created with `clone()` are not
+    /// observed.
+    pub fn observer(&self) -> Observer {
+        self.observed.observer()
+    }
+
+    /// Check that the model is available on the endpoint, normalizing its name to the one in
+    /// the model catalog, e.g., "GPT-4o" to "gpt-4o".
+    ///
+    /// Endpoints without a model catalog, e.g., Azure OpenAI deployments, are not validated.
+    pub async fn validate_model(&mut self) -> Result<(), Error> {
+        let response = self.client.get(self.provider.models_endpoint()).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+
+        let models = if self.provider == Provider::Ollama {
+            OpenAiClient::read_response::<ollama_api::Tags>(response)
+                .await?
+                .into()
+        } else {
+            OpenAiClient::read_response::<ModelList>(response).await?
+        };
+
+        match models.resolve(&self.model) {
+            Ok(model) => {
+                self.model = model.to_string();
+                Ok(())
+            }
+            Err(suggestion) => Err(Error::UnknownModel {
+                model: self.model.clone(),
+                suggestion: suggestion.map(ToString::to_string),
+            }),
+        }
+    }
+
+    /// Fork the conversation into an independent client, e.g., to explore an alternative
+    /// continuation. Same as `clone()`.
+    ///
+    /// The fork starts with the same context and shares the HTTP connection pool, the tokenizer,
+    /// the rate limits and the event subscribers, but further exchanges are not shared between
+    /// the two. The fork is not observed by the [`Observer`]s of this client and doesn't
+    /// persist its exchanges to the context store. Use [`ChatClient::push_exchange`] to merge
+    /// an exchange of the fork back.
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Remove the most recent request-response pair from the conversation context and return it,
+    /// e.g., to forget a wrong answer.
+    ///
+    /// The exchange already persisted to the [`ContextStore`] is not removed.
+    pub fn pop_last_exchange(&mut self) -> Option<Exchange> {
+        let exchange = self.context.pop()?;
+
+        self.tool_turn = None;
+        self.observed.reset(self.context.exchanges());
+
+        Some(exchange)
+    }
+
+    /// Save the conversation context as checkpoint `name`, replacing the checkpoint with
+    /// the same name. Checkpoints are kept in memory and leave out the ephemeral exchanges.
+    pub fn checkpoint(&mut self, name: impl Into<String>) {
+        self.context.snapshot(name.into());
+    }
+
+    /// Roll the conversation context back to checkpoint `name`, discarding the exchanges since.
+    /// The checkpoint is kept, so the conversation can be rolled back to it again.
+    ///
+    /// The exchanges already persisted to the [`ContextStore`] are not removed.
+    ///
+    /// Returns `false` if there is no such checkpoint.
+    pub fn rollback(&mut self, name: &str) -> bool {
+        if !self.context.restore(name) {
+            return false;
+        }
+
+        self.tool_turn = None;
+        self.observed.reset(self.context.exchanges());
+
+        true
+    }
+
+    /// Names of the checkpoints, oldest first.
+    pub fn checkpoints(&self) -> Vec<&str> {
+        self.context.snapshots().collect()
+    }
+
+    /// Model used for the requests.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Request parameters in effect for subsequent requests: the ones of [`ChatClientConfig`]
+    /// and the stop sequences set with [`ChatClient::set_stop_sequences`]. Unset parameters
+    /// are left to the provider defaults. The model is [`ChatClient::model`].
+    pub fn effective_options(&self) -> RequestOptions {
+        RequestOptions {
+            stop: Some(self.stop.clone()),
+            ..self.defaults.clone()
+        }
+    }
+
+    /// Sequences where the API will stop generating further tokens in subsequent requests.
+    pub fn stop_sequences(&self) -> &[String] {
+        &self.stop
+    }
+
+    /// Set up to 4 sequences where the API will stop generating further tokens in subsequent
+    /// requests. Pass an empty vector to disable stop sequences.
+    pub fn set_stop_sequences(&mut self, stop: Vec<String>) {
+        self.stop = stop;
+    }
+
+    /// Speaker of the subsequent requests in multi-speaker mode, if set.
+    pub fn speaker(&self) -> Option<&str> {
+        self.context.speaker()
+    }
+
+    /// Attribute the subsequent requests to `speaker`, e.g., a user of a chat room bridged to
+    /// the model, and keep the speakers of the requests in the context, so the model can tell
+    /// them apart. Pass `None` to stop naming the requests.
+    ///
+    /// The name is sent in the `name` field of the user messages. OpenAI only accepts letters,
+    /// digits, underscores and dashes, up to 64 characters. The speakers are not persisted to
+    /// the [`ContextStore`].
+    pub fn set_speaker(&mut self, speaker: Option<String>) {
+        self.context.set_speaker(speaker);
+    }
+
+    /// Make the subsequent exchanges ephemeral, e.g., for an incognito mode: they are sent to
+    /// the provider as usual, but neither persisted to the [`ContextStore`], nor reported to
+    /// the exchange hook and the [`Observer`]s, nor saved in checkpoints, and stay in the context
+    /// for the next `turns` requests only. Pass `None` to stop.
+    ///
+    /// Forks of the client created with `clone()` inherit the setting.
+    pub fn set_ephemeral(&mut self, turns: Option<usize>) {
+        self.context.set_ephemeral(turns);
+    }
+
+    /// Number of the following requests new exchanges stay in the context for, if they are
+    /// ephemeral. See [`ChatClient::set_ephemeral`].
+    pub fn ephemeral(&self) -> Option<usize> {
+        self.context.ephemeral()
+    }
+
+    /// Attach the `feedback` to the latest exchange in the context, replacing the previous one.
+    ///
+    /// The feedback is kept with the exchange in [`ChatClient::history`], e.g., for exporting
+    /// the rated exchanges. It is not sent to the model.
+    pub fn set_feedback(&mut self, feedback: Feedback) -> Result<&Exchange, Error> {
+        self.context.set_feedback(feedback).ok_or(Error::NoExchange)
+    }
+
+    /// Extend the context with an exchange that happened elsewhere, e.g., on a forked client.
+    pub fn push_exchange(&mut self, request: String, response: String) {
+        let exchange = self.context.exchange(request, response);
+        self.push_to_context(exchange, None);
+    }
+
+    /// Extend the context with the `exchange`, accounting it with the prompt and completion
+    /// tokens reported by the provider if known.
+    fn push_to_context(&mut self, exchange: Exchange, usage: Option<(usize, usize)>) {
+        if let Some(store) = self
+            .context_store
+            .0
+            .as_ref()
+            .filter(|_| !exchange.is_ephemeral())
+        {
+            if store.push(exchange.request(), exchange.response()).is_err() {
+                self.emit(Event::Warning(Warning::NotPersisted));
+            }
+        }
+
+        // Ephemeral exchanges are not observed.
+        let turn = (!exchange.is_ephemeral()).then(|| Turn {
+            request: exchange.request().to_string(),
+            response: exchange.response().to_string(),
+        });
+        let observed = |context: &Context| {
+            context
+                .exchanges()
+                .iter()
+                .filter(|exchange| !exchange.is_ephemeral())
+                .count()
+        };
+        let exchanges = observed(&self.context);
+        let discarded = self.context.push_exchange(exchange, usage);
+        let removed = exchanges + usize::from(turn.is_some()) - observed(&self.context);
+        match turn {
+            Some(turn) => self.observed.push(turn, removed, self.context.exchanges()),
+            None if removed > 0 => self.observed.reset(self.context.exchanges()),
+            None => {}
+        }
+
+        if discarded > 0 {
+            self.emit(Event::Warning(Warning::ContextTruncated { discarded }));
+        }
+    }
+
+    /// Request chat completions, refreshing the credentials and retrying once if they expired.
+    async fn chat_completions(
+        &mut self,
+        body: ChatCompletionsBody,
+    ) -> Result<ChatCompletions, Error> {
+        if let Some(parameter) = self.provider.unsupported_parameter(&body) {
+            return Err(Error::UnsupportedParameter {
+                provider: self.provider,
+                parameter,
+            });
+        }
+
+        self.emit(Event::RequestStarted);
+
+        // Subscribing marks the aborts so far as seen.
+        let mut aborted = self.abort.0.generation.subscribe();
+        let request = async {
+            match self.send_request(&body).await {
+                Err(error) if error.is_auth_error() => {
+                    self.refresh_auth_and_retry(&body, error).await
+                }
+                result => result.map_err(Into::into),
+            }
+        };
+        let result = tokio::select! {
+            result = request => result,
+            _ = aborted.changed() => Err(Error::Cancelled),
+        };
+
+        self.emit(Event::RequestFinished {
+            success: result.is_ok(),
+        });
+
+        result
+    }
+
+    /// Refresh the credentials with the callback and retry the request once.
+    async fn refresh_auth_and_retry(
+        &mut self,
+        body: &ChatCompletionsBody,
+        error: OpenAiClientError,
+    ) -> Result<ChatCompletions, Error> {
+        let Some(on_auth_expired) = &self.on_auth_expired else {
+            return Err(error.into());
+        };
+        let Some(auth) = on_auth_expired().await else {
+            return Err(error.into());
+        };
+
+        self.client.set_auth(auth)?;
+        self.emit(Event::Warning(Warning::AuthRetried));
+
+        Ok(self.send_request(body).await?)
+    }
+
+    /// Send a single request to the API.
+    async fn send_request(
+        &self,
+        body: &ChatCompletionsBody,
+    ) -> Result<ChatCompletions, OpenAiClientError> {
+        let prompt_tokens = self
+            .rate_limit_tokenizer
+            .as_ref()
+            .map(|tokenizer| {
+                body.messages
+                    .iter()
+                    .filter_map(GenericMessage::content)
+                    .map(|content| tokenizer.encode_with_special_tokens(content).len())
+                    .sum()
+            })
+            .unwrap_or_default();
+
+        if self.provider == Provider::Ollama {
+            let body = ollama_api::ChatBody::new(body, &self.ollama_options);
+            let response = self.client.send(&body, prompt_tokens).await?;
+            self.emit(Event::ResponseStarted);
+
+            return OpenAiClient::read_response::<ollama_api::ChatResponse>(response)
+                .await
+                .map(Into::into);
+        }
+
+        let response = self.client.send(body, prompt_tokens).await?;
+        self.emit(Event::ResponseStarted);
+
+        OpenAiClient::read_response(response).await
+    }
+
+    /// Input and output tokens used, warning if the usage is missing.
+    fn token_usage(&self, usage: Option<Usage>) -> (usize, usize) {
+        match usage {
+            Some(usage) => {
+                self.observed
+                    .add_usage(usage.prompt_tokens, usage.completion_tokens);
+                (usage.prompt_tokens, usage.completion_tokens)
+            }
+            None => {
+                self.emit(Event::Warning(Warning::UsageMissing));
+                (0, 0)
+            }
+        }
+    }
+
+    /// Notify the subscribers, if any.
+    fn emit(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+
+    /// Request-response pairs in the conversation context, oldest first.
+    pub fn exchanges(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.history()
+            .map(|exchange| (exchange.request(), exchange.response()))
+    }
+
+    /// Exchanges in the conversation context with their speakers and token counts, oldest
+    /// first. These are the exchanges sent with the next request.
+    pub fn history(&self) -> impl Iterator<Item = &Exchange> {
+        self.context.exchanges().iter()
+    }
+
+    /// Conversation history with the request-response pairs that will be discarded from the
+    /// context after the next exchange marked.
+    ///
+    /// As the size of the next exchange is not known in advance, it is assumed to be the same
+    /// as the size of the latest one.
+    pub fn eviction_preview(&self) -> Vec<TurnPreview<'_>> {
+        let tokens = self.context.exchange_tokens();
+        let next_exchange_tokens = tokens
+            .as_ref()
+            .and_then(|tokens| tokens.last().copied())
+            .unwrap_or_default();
+        let evicted = self.context.eviction_dry_run(next_exchange_tokens);
+
+        self.context
+            .exchanges()
+            .iter()
+            .enumerate()
+            .map(|(index, exchange)| TurnPreview {
+                request: exchange.request(),
+                response: exchange.response(),
+                tokens: tokens.as_ref().map(|tokens| tokens[index]),
+                evicted: evicted.contains(&index),
+            })
+            .collect()
+    }
+
+    /// Tokens in the conversation context split by role, including the system message
+    /// of [`ChatClientConfig::system_message_provider`].
+    ///
+    /// If the context is not truncated, a tokenizer is initialized for counting.
+    pub fn context_stats(&self) -> Result<TokenBreakdown, Error> {
+        self.with_tokenizer(|tokenizer| {
+            let mut breakdown = self.context.tokens_with(tokenizer);
+            if let Some(provider) = &self.system_message_provider {
+                breakdown.system += tokenizer.encode_with_special_tokens(&provider()).len();
+            }
+
+            breakdown
+        })
+    }
+
+    /// Estimated number of tokens in the `text`, adjusted by [`ChatClient::token_correction`].
+    ///
+    /// Nothing is sent to the API. If the context is not truncated, a tokenizer is
+    /// initialized for counting.
+    pub fn count_tokens(&self, text: &str) -> Result<usize, Error> {
+        self.with_tokenizer(|tokenizer| {
+            self.context
+                .corrected(tokenizer.encode_with_special_tokens(text).len())
+        })
+    }
+
+    /// Estimated number of tokens in the prompt for the `request`: the system message,
+    /// the conversation context and the request itself.
+    ///
+    /// The estimate doesn't include the per-message overhead of the chat format and is adjusted
+    /// by [`ChatClient::token_correction`].
+    pub fn estimate_prompt_tokens(&self, request: &str) -> Result<usize, Error> {
+        self.with_tokenizer(|tokenizer| {
+            let num_tokens = |m: &str| tokenizer.encode_with_special_tokens(m).len();
+
+            let dynamic_system_tokens = self
+                .system_message_provider
+                .as_ref()
+                .map(|provider| num_tokens(&provider()))
+                .unwrap_or_default();
+
+            self.context.corrected(
+                self.context.tokens_with(tokenizer).total()
+                    + dynamic_system_tokens
+                    + num_tokens(request),
+            )
+        })
+    }
+
+    /// Run `f` with the tokenizer of the context, or with a new one if the context is not
+    /// truncated.
+    fn with_tokenizer<T>(&self, f: impl FnOnce(&tiktoken_rs::CoreBPE) -> T) -> Result<T, Error> {
+        match self.context.tokenizer() {
+            Some(tokenizer) => Ok(f(tokenizer)),
+            None => Ok(f(&*chat_tokenizer()?)),
+        }
+    }
+
+    /// Ratio of the prompt tokens reported by the provider to the tokenizer estimates,
+    /// smoothed over the exchanges. Applied to the estimates of the rolling window, e.g., for
+    /// models with a tokenizer different from `o200k_base`.
+    ///
+    /// 1.0 if the context is not truncated or the provider reported no usage yet.
+    pub fn token_correction(&self) -> f64 {
+        self.context.token_correction()
+    }
+
+    /// Estimated tokens of the system message of [`ChatClientConfig::system_message_provider`]
+    /// in the request `body`, zero without the provider.
+    ///
+    /// Returns `None` if the context is not truncated, i.e., has no tokenizer.
+    fn dynamic_system_tokens(&self, body: &ChatCompletionsBody) -> Option<usize> {
+        let tokenizer = self.context.tokenizer()?;
+
+        Some(
+            body.messages
+                .first()
+                .filter(|_| self.system_message_provider.is_some())
+                .filter(|message| message.role() == message::Role::System)
+                .and_then(GenericMessage::content)
+                .map(|content| {
+                    self.context
+                        .corrected(tokenizer.encode_with_special_tokens(content).len())
+                })
+                .unwrap_or_default(),
+        )
+    }
+
     /// Construct a request body.
-    fn body(model: String, context: &Context, request: String) -> ChatCompletionsBody {
+    fn body(&self, request: String) -> ChatCompletionsBody {
+        let messages = match &self.system_message_provider {
+            Some(provider) => Some(provider())
+                .filter(|system_message| !system_message.is_empty())
+                .map(|system_message| SystemMessage::new(system_message).into())
+                .into_iter()
+                .chain(self.context.conversation_with_request(request))
+                .map(Into::into)
+                .collect(),
+            None => self.context.with_request(request).map(Into::into).collect(),
+        };
+
+        let mut body = ChatCompletionsBody {
+            model: self.model.clone(),
+            messages,
+            stop: self.stop.clone(),
+            tools: self.tools.clone(),
+            ..Default::default()
+        };
+        self.defaults.clone().apply(&mut body);
+
+        body
+    }
+
+    /// Construct a request body asking the judge to score the candidates.
+    fn judge_body(judge: JudgeConfig, request: &str, candidates: &[String]) -> ChatCompletionsBody {
+        let instructions = judge
+            .instructions
+            .unwrap_or_else(|| String::from(DEFAULT_JUDGE_INSTRUCTIONS));
+
+        let mut prompt = format!("Question:\n{request}\n");
+        for (index, candidate) in candidates.iter().enumerate() {
+            prompt += &format!("\nCandidate {}:\n{candidate}\n", index + 1);
+        }
+        prompt += "\nRespond with the scores of all candidates in the order they are listed.";
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "scores": { "type": "array", "items": { "type": "number" } },
+            },
+            "required": ["scores"],
+            "additionalProperties": false,
+        });
+
         ChatCompletionsBody {
-            model,
-            messages: context.with_request(request).map(Into::into).collect(),
+            model: judge.model,
+            messages: vec![
+                SystemMessage::new(instructions).into(),
+                UserMessage::new(prompt).into(),
+            ],
+            response_format: Some(
+                ResponseFormat::json_schema("scores", schema)
+                    .strict(true)
+                    .build(),
+            ),
             ..Default::default()
         }
     }
 }
 
+/// Judge scores, as requested from the judge model.
+#[derive(Deserialize)]
+struct Scores {
+    scores: Vec<f64>,
+}
+
+/// Response of a candidate choice with its safety information. Flagged candidates without
+/// content are empty.
+fn candidate(
+    choice: CompletionChoice,
+    prompt_filter_results: Option<Value>,
+) -> Result<(String, SafetyInfo), Error> {
+    let content_filtered = choice.finish_reason == "content_filter";
+    let assistant_message = AssistantMessage::try_from(choice.message)?;
+    let safety = SafetyInfo {
+        refusal: assistant_message.refusal,
+        content_filtered,
+        content_filter_results: choice.content_filter_results,
+        prompt_filter_results,
+        moderation: None,
+    };
+
+    match assistant_message.content {
+        Some(content) => Ok((content, safety)),
+        None if safety.is_flagged() => Ok((String::new(), safety)),
+        None => Err(Error::NoContent),
+    }
+}
+
+/// Extract the response text from a completion choice.
+fn response_content(choice: CompletionChoice) -> Result<String, Error> {
+    let assistant_message = AssistantMessage::try_from(choice.message)?;
+
+    assistant_message.content.ok_or(
+        assistant_message
+            .refusal
+            .map_or(Error::NoContent, Error::Refusal),
+    )
+}
+
 fn ensure_trailing_slash(url: String) -> String {
     if url.ends_with('/') {
         url
@@ -213,21 +1886,304 @@ fn ensure_trailing_slash(url: String) -> String {
     }
 }
 
+/// Tokenizer to estimate the prompt tokens with, if the tokens per minute are limited.
+fn rate_limit_tokenizer(
+    rate_limits: Option<RateLimits>,
+) -> Result<Option<Arc<tiktoken_rs::CoreBPE>>, Error> {
+    rate_limits
+        .and_then(|limits| limits.tokens_per_minute)
+        .map(|_| chat_tokenizer())
+        .transpose()
+}
+
+/// Shared tokenizer of the chat models.
+fn chat_tokenizer() -> Result<Arc<tiktoken_rs::CoreBPE>, Error> {
+    SharedResources::global()
+        .chat_tokenizer()
+        .map_err(|e| Error::TokenizerInit(format!("{e}")))
+}
+
 fn create_context(
     system_message: Option<String>,
+    initial_context: Option<InitialContext>,
+    history: Vec<(String, String)>,
+    dynamic_system_message: bool,
     min_history_tokens: Option<usize>,
     max_history_tokens: Option<usize>,
+    strategy: ContextStrategy,
 ) -> Result<Context, Error> {
-    let context = if min_history_tokens.is_some() || max_history_tokens.is_some() {
+    let (initial_system_message, conversation) = initial_context
+        .map(InitialContext::into_parts)
+        .unwrap_or_default();
+    // A dynamic system message replaces the static ones and is not part of the history.
+    let system_message = if dynamic_system_message {
+        None
+    } else {
+        initial_system_message.or(system_message)
+    };
+
+    let mut context = if min_history_tokens.is_some() || max_history_tokens.is_some() {
         Context::new_with_rolling_window(
             system_message,
-            tiktoken_rs::o200k_base().map_err(|e| Error::TokenizerInit(format!("{e}")))?,
+            chat_tokenizer()?,
             min_history_tokens,
             max_history_tokens,
         )
+        .with_strategy(strategy)
     } else {
         Context::new(system_message)
     };
 
+    for (request, response) in conversation.into_iter().chain(history) {
+        context.push(request, response);
+    }
+
     Ok(context)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_client::mock::mock_api;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    /// Chat completion response with a choice per response.
+    fn completion(model: &str, responses: &[&str]) -> (u16, Value) {
+        let choices = responses
+            .iter()
+            .enumerate()
+            .map(|(index, content)| {
+                json!({
+                    "finish_reason": "stop",
+                    "index": index,
+                    "message": { "role": "assistant", "content": content },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        (
+            200,
+            json!({
+                "id": "chatcmpl-1",
+                "choices": choices,
+                "created": 0,
+                "model": model,
+                "object": "chat.completion",
+                "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 },
+            }),
+        )
+    }
+
+    fn client(url: String, on_exchange: Option<ExchangeHook>) -> ChatClient {
+        ChatClient::new(
+            Auth::Token(String::from("token")),
+            ChatClientConfig {
+                api_url: url,
+                model: String::from("chat-model"),
+                on_exchange,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    /// Exchange hook collecting the summaries.
+    fn exchange_hook() -> (ExchangeHook, Arc<Mutex<Vec<ExchangeSummary>>>) {
+        let summaries = Arc::new(Mutex::new(Vec::new()));
+        let collected = Arc::clone(&summaries);
+
+        (
+            Arc::new(move |summary| collected.lock().unwrap().push(summary)),
+            summaries,
+        )
+    }
+
+    #[tokio::test]
+    async fn best_of() {
+        let (url, requests) = mock_api(
+            vec![
+                completion("cheap-model", &["first", "second"]),
+                completion("judge-model", &[r#"{"scores":[1,3]}"#]),
+            ],
+            Duration::ZERO,
+        );
+        let (hook, summaries) = exchange_hook();
+        let mut chat = client(url, Some(hook));
+        let judge = JudgeConfig {
+            model: String::from("judge-model"),
+            candidate_model: Some(String::from("cheap-model")),
+            instructions: None,
+        };
+
+        assert!(matches!(
+            chat.best_of(String::from("Question?"), 0, judge.clone())
+                .await,
+            Err(Error::NoCandidatesRequested)
+        ));
+
+        let best = chat
+            .best_of(String::from("Question?"), 2, judge)
+            .await
+            .unwrap();
+        assert_eq!((best.response.as_str(), best.index), ("second", 1));
+        let history = chat
+            .history()
+            .map(|exchange| (exchange.request(), exchange.response()))
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![("Question?", "second")]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0]["model"], "cheap-model");
+        assert_eq!(requests[0]["n"], 2);
+        assert_eq!(requests[1]["model"], "judge-model");
+
+        let summaries = summaries.lock().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].model, "cheap-model");
+        assert_eq!(summaries[0].response.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn auth_refresh_retries_once() {
+        let unauthorized = (401, json!({ "error": { "message": "Invalid token" } }));
+        let refreshes = Arc::new(AtomicUsize::new(0));
+        let chat = |url| {
+            let refreshes = Arc::clone(&refreshes);
+            ChatClient::new(
+                Auth::Token(String::from("expired")),
+                ChatClientConfig {
+                    api_url: url,
+                    on_auth_expired: Some(Arc::new(move || {
+                        refreshes.fetch_add(1, Ordering::Relaxed);
+                        Box::pin(async { Some(Auth::Token(String::from("refreshed"))) })
+                    })),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+
+        let (url, requests) = mock_api(
+            vec![unauthorized.clone(), completion("gpt-4o-mini", &["Hi!"])],
+            Duration::ZERO,
+        );
+        let response = chat(url).ask(String::from("Hello")).await.unwrap();
+        assert_eq!(response, "Hi!");
+        assert_eq!(refreshes.load(Ordering::Relaxed), 1);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        // The retry is not repeated if the refreshed credentials are rejected as well.
+        let (url, requests) = mock_api(
+            vec![unauthorized.clone(), unauthorized.clone(), unauthorized],
+            Duration::ZERO,
+        );
+        let error = chat(url).ask(String::from("Hello")).await.unwrap_err();
+        assert!(matches!(error, Error::OpenAiClient(ref error) if error.is_auth_error()));
+        assert_eq!(refreshes.load(Ordering::Relaxed), 2);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn openrouter_moderation() {
+        let flagged = (
+            403,
+            json!({
+                "error": {
+                    "code": 403,
+                    "message": "openai/gpt-4o requires moderation on OpenAI. Your input was \
+                                flagged for \"harassment\". No credits were charged.",
+                    "metadata": {
+                        "reasons": ["harassment"],
+                        "flagged_input": "...you are an idiot",
+                        "provider_name": "OpenAI",
+                        "model_slug": "openai/gpt-4o",
+                    },
+                },
+            }),
+        );
+        let (url, requests) = mock_api(vec![flagged.clone(), flagged], Duration::ZERO);
+        let mut chat = client(url, None);
+
+        let completion = chat
+            .request_completion(String::from("You are an idiot"))
+            .await
+            .unwrap();
+        assert!(completion.safety.is_flagged());
+        assert_eq!(
+            completion.safety.moderation.unwrap()["reasons"],
+            json!(["harassment"])
+        );
+        assert_eq!(chat.history().count(), 0);
+
+        assert!(matches!(
+            chat.ask(String::from("You are an idiot")).await,
+            Err(Error::Moderated(reasons)) if reasons == "harassment"
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn ephemeral_exchanges_not_observed() {
+        let mut chat = client(String::from("http://localhost:8080/"), None);
+        let observer = chat.observer();
+        let mut turns = observer.subscribe();
+        let pair = |request: &str, response: &str| (request.to_string(), response.to_string());
+
+        chat.push_exchange(String::from("Hi!"), String::from("Hello!"));
+        chat.set_ephemeral(Some(1));
+        chat.push_exchange(String::from("Secret?"), String::from("Secret!"));
+        chat.checkpoint("incognito");
+        assert_eq!(chat.history().count(), 2);
+        assert_eq!(observer.exchanges(), vec![pair("Hi!", "Hello!")]);
+
+        chat.set_ephemeral(None);
+        chat.push_exchange(String::from("Bye!"), String::from("Bye!"));
+        chat.push_exchange(String::from("Really?"), String::from("Yes."));
+        assert_eq!(chat.history().count(), 3);
+        assert_eq!(
+            observer.exchanges(),
+            vec![
+                pair("Hi!", "Hello!"),
+                pair("Bye!", "Bye!"),
+                pair("Really?", "Yes.")
+            ]
+        );
+
+        let requests = std::iter::from_fn(|| turns.try_recv().ok())
+            .map(|turn| turn.request)
+            .collect::<Vec<_>>();
+        assert_eq!(requests, ["Hi!", "Bye!", "Really?"]);
+
+        assert!(chat.rollback("incognito"));
+        assert_eq!(observer.exchanges(), vec![pair("Hi!", "Hello!")]);
+        assert_eq!(chat.history().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn abort_in_flight() {
+        let delay = Duration::from_secs(5);
+        let (url, requests) = mock_api(vec![completion("chat-model", &["Too late"])], delay);
+        let mut chat = client(url, None);
+        chat.push_exchange(String::from("Hi!"), String::from("Hello!"));
+        let abort = chat.abort_handle();
+
+        let started = std::time::Instant::now();
+        let (result, ()) = tokio::join!(chat.ask(String::from("Slow question?")), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            abort.abort();
+        });
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(started.elapsed() < delay);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let history = chat
+            .history()
+            .map(|exchange| (exchange.request(), exchange.response()))
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![("Hi!", "Hello!")]);
+    }
+}
@@ -22,19 +22,178 @@
 
 //! Chatbot context.
 
-use crate::chat_client::openai_api::message::{
-    AssistantMessage, Message, SystemMessage, UserMessage,
+use crate::chat_client::{
+    openai_api::message::{AssistantMessage, Message, SystemMessage, UserMessage},
+    shared::SharedResources,
 };
 use iter_accumulate::IterAccumulate;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::Arc, time::SystemTime};
+
+/// Requests of at most that many tokens are considered acknowledgements by
+/// [`ContextStrategy::Smart`], e.g., "thanks!" or "ok, got it".
+const ACKNOWLEDGEMENT_MAX_TOKENS: usize = 5;
+
+/// Weight of the latest provider-reported pair in the tokenizer correction factor.
+const CORRECTION_SMOOTHING: f64 = 0.25;
+
+/// Bounds of the correction factor of a single pair, so outliers don't skew the estimates.
+const CORRECTION_BOUNDS: (f64, f64) = (0.5, 2.0);
+
+/// Strategy of discarding request-response pairs to keep the context within the limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Discard the oldest pairs.
+    #[default]
+    OldestFirst,
+    /// Discard low-value pairs first: requests repeated later in the conversation and short
+    /// acknowledgements like "thanks!". Then discard the oldest pairs.
+    ///
+    /// The latest pair is never considered low-value.
+    Smart,
+}
+
+/// Tokens in the context split by message role.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBreakdown {
+    /// Tokens in the system message.
+    pub system: usize,
+    /// Tokens in the user requests.
+    pub user: usize,
+    /// Tokens in the assistant responses.
+    pub assistant: usize,
+}
+
+impl TokenBreakdown {
+    /// Total number of tokens in the context.
+    pub fn total(&self) -> usize {
+        self.system + self.user + self.assistant
+    }
+}
+
+/// Rating of an exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rating {
+    /// Helpful response.
+    Good,
+    /// Unhelpful or wrong response.
+    Bad,
+}
+
+/// Feedback on an exchange, e.g., collected for tuning the prompts or the model later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Feedback {
+    /// Rating of the response.
+    pub rating: Rating,
+    /// Optional comment of the user.
+    pub comment: Option<String>,
+}
+
+/// Request-response pair in the conversation context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    request: String,
+    response: String,
+    speaker: Option<String>,
+    /// Tokenizer estimate, `None` without tokenizer.
+    estimated_tokens: Option<usize>,
+    /// Tokens reported by the provider, if known.
+    reported_tokens: Option<usize>,
+    timestamp: Option<SystemTime>,
+    completion_id: Option<String>,
+    model: Option<String>,
+    /// Number of the following requests an ephemeral pair stays in the context for.
+    ephemeral_turns: Option<usize>,
+    feedback: Option<Feedback>,
+}
+
+impl Exchange {
+    /// User request.
+    pub fn request(&self) -> &str {
+        &self.request
+    }
+
+    /// Assistant response.
+    pub fn response(&self) -> &str {
+        &self.response
+    }
+
+    /// Speaker of the request in multi-speaker mode, see
+    /// [`ChatClient::set_speaker`](crate::ChatClient::set_speaker).
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
+    /// Tokens of the request and the response: the count reported by the provider if known,
+    /// otherwise the tokenizer estimate.
+    ///
+    /// `None` if the context is not truncated, i.e., has no tokenizer.
+    pub fn tokens(&self) -> Option<usize> {
+        self.reported_tokens.or(self.estimated_tokens)
+    }
+
+    /// Time the response was received.
+    ///
+    /// `None` for the exchanges not completed by the client, e.g., of the initial history or
+    /// pushed with [`ChatClient::push_exchange`](crate::ChatClient::push_exchange).
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    /// ID of the chat completion assigned by the provider, if any.
+    pub fn completion_id(&self) -> Option<&str> {
+        self.completion_id.as_deref()
+    }
+
+    /// Model that served the completion as reported by the provider, e.g., a dated version of
+    /// the requested model.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Whether the pair is ephemeral, see
+    /// [`ChatClient::set_ephemeral`](crate::ChatClient::set_ephemeral).
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral_turns.is_some()
+    }
+
+    /// Feedback on the pair, see [`ChatClient::set_feedback`](crate::ChatClient::set_feedback).
+    pub fn feedback(&self) -> Option<&Feedback> {
+        self.feedback.as_ref()
+    }
+
+    /// Record the completion the response was received with.
+    pub(crate) fn with_completion(mut self, id: String, model: String) -> Self {
+        self.timestamp = Some(SystemTime::now());
+        self.completion_id = Some(id).filter(|id| !id.is_empty());
+        self.model = Some(model).filter(|model| !model.is_empty());
+        self
+    }
+}
 
 /// Chatbot context.
+///
+/// Clones share the tokenizer.
 #[derive(Debug, Default, Clone)]
 pub struct Context {
     system_message: Option<String>,
-    conversation: Vec<(String, String)>,
-    tokenizer: Option<tiktoken_rs::CoreBPE>,
+    conversation: Vec<Exchange>,
+    /// Speaker of the next request.
+    speaker: Option<String>,
+    /// Ratio of the provider-reported tokens to the tokenizer estimates, smoothed over
+    /// the reported pairs, and the model it was measured for. `None` until the first pair
+    /// is reported.
+    token_correction: Option<(Option<String>, f64)>,
+    tokenizer: Option<Arc<tiktoken_rs::CoreBPE>>,
     min_history_tokens: Option<usize>,
     max_history_tokens: Option<usize>,
+    strategy: ContextStrategy,
+    /// Named snapshots of the conversation, oldest first.
+    snapshots: Vec<(String, Vec<Exchange>)>,
+    /// Number of the following requests new pairs stay in the context for, if ephemeral.
+    ephemeral_turns: Option<usize>,
 }
 
 impl Context {
@@ -43,16 +202,21 @@ impl Context {
         Self {
             system_message,
             conversation: Vec::new(),
+            speaker: None,
+            token_correction: None,
             tokenizer: None,
             min_history_tokens: None,
             max_history_tokens: None,
+            strategy: ContextStrategy::default(),
+            snapshots: Vec::new(),
+            ephemeral_turns: None,
         }
     }
 
     /// Create a new chat context wth tokenizer.
     pub fn new_with_rolling_window(
         system_message: Option<String>,
-        tokenizer: tiktoken_rs::CoreBPE,
+        tokenizer: impl Into<Arc<tiktoken_rs::CoreBPE>>,
         min_history_tokens: Option<usize>,
         max_history_tokens: Option<usize>,
     ) -> Self {
@@ -61,70 +225,530 @@ impl Context {
         Self {
             system_message,
             conversation: Vec::new(),
-            tokenizer: Some(tokenizer),
+            speaker: None,
+            token_correction: None,
+            tokenizer: Some(tokenizer.into()),
             min_history_tokens,
             max_history_tokens,
+            strategy: ContextStrategy::default(),
+            snapshots: Vec::new(),
+            ephemeral_turns: None,
         }
     }
 
+    /// Set the strategy of discarding request-response pairs.
+    pub fn with_strategy(mut self, strategy: ContextStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Name the next requests with `speaker`, e.g., a user of a chat room, so the model can tell
+    /// the speakers apart. The name is kept for the requests in the context.
+    pub fn set_speaker(&mut self, speaker: Option<String>) {
+        self.speaker = speaker;
+    }
+
+    /// Make the new pairs ephemeral: they stay in the context for the next `turns` requests
+    /// only. Pass `None` to keep the new pairs as usual.
+    pub fn set_ephemeral(&mut self, turns: Option<usize>) {
+        self.ephemeral_turns = turns;
+    }
+
+    /// Number of the following requests new pairs stay in the context for, if ephemeral.
+    pub fn ephemeral(&self) -> Option<usize> {
+        self.ephemeral_turns
+    }
+
+    /// Speaker of the next request, if set.
+    pub fn speaker(&self) -> Option<&str> {
+        self.speaker.as_deref()
+    }
+
     /// Context so far with a new request message.
     pub fn with_request(&self, request: String) -> impl Iterator<Item = Message> + '_ {
         self.system_message
             .iter()
             .map(|system_message| SystemMessage::new(system_message.clone()).into())
-            .chain(self.conversation.iter().flat_map(|(request, response)| {
+            .chain(self.conversation_with_request(request))
+    }
+
+    /// Conversation so far with a new request message, without the system message.
+    pub fn conversation_with_request(&self, request: String) -> impl Iterator<Item = Message> + '_ {
+        let user_message = |request, speaker: Option<String>| match speaker {
+            Some(speaker) => UserMessage::with_name(request, speaker),
+            None => UserMessage::new(request),
+        };
+
+        self.conversation
+            .iter()
+            .flat_map(move |exchange| {
                 [
-                    UserMessage::new(request.clone()).into(),
-                    AssistantMessage::new(response.clone()).into(),
+                    user_message(exchange.request.clone(), exchange.speaker.clone()).into(),
+                    AssistantMessage::new(exchange.response.clone()).into(),
                 ]
                 .into_iter()
-            }))
-            .chain(std::iter::once(UserMessage::new(request).into()))
+            })
+            .chain(std::iter::once(
+                user_message(request, self.speaker.clone()).into(),
+            ))
     }
 
     /// Extend the context with a new pair of request and response.
-    pub fn push(&mut self, request: String, response: String) {
-        self.conversation.push((request, response));
-        self.keep_recent();
+    ///
+    /// Returns the number of old pairs discarded to keep the context within the limits.
+    pub fn push(&mut self, request: String, response: String) -> usize {
+        let exchange = self.exchange(request, response);
+        self.push_exchange(exchange, None)
+    }
+
+    /// Extend the context with the `exchange` created with [`Context::exchange`].
+    ///
+    /// If `usage` is set, the exchange is accounted with the prompt and completion tokens
+    /// reported by the provider instead of the tokenizer estimate. The ratio of the reported
+    /// tokens to the estimate adjusts the correction factor applied to the estimates of
+    /// the system message and the pairs without reported usage.
+    ///
+    /// The prompt tokens must only cover the context and the request: the tokens of
+    /// the response are the completion tokens, and the tokens of the request are what remains of
+    /// the prompt tokens after the system message and the pairs already in the context.
+    ///
+    /// Ephemeral pairs that stayed in the context for their number of requests are removed,
+    /// and an ephemeral pair staying for no requests is not added.
+    ///
+    /// Returns the number of old pairs discarded to keep the context within the limits, not
+    /// counting the expired ephemeral pairs.
+    pub fn push_exchange(
+        &mut self,
+        mut exchange: Exchange,
+        usage: Option<(usize, usize)>,
+    ) -> usize {
+        let Some((prompt_tokens, completion_tokens)) = usage else {
+            self.expire_ephemeral();
+            if exchange.ephemeral_turns != Some(0) {
+                self.conversation.push(exchange);
+            }
+            return self.keep_recent();
+        };
+
+        exchange.reported_tokens = self.exchange_tokens().and_then(|exchange_tokens| {
+            let prefix = self.system_tokens() + exchange_tokens.iter().sum::<usize>();
+            let request_tokens = prompt_tokens.checked_sub(prefix).filter(|t| *t > 0)?;
+
+            Some(request_tokens + completion_tokens)
+        });
+
+        if let (Some(estimated), Some(reported)) =
+            (exchange.estimated_tokens, exchange.reported_tokens)
+        {
+            if estimated > 0 {
+                let (min, max) = CORRECTION_BOUNDS;
+                let ratio = (reported as f64 / estimated as f64).clamp(min, max);
+                // Tokenizers differ between the models, so the correction restarts on a change.
+                let correction = match self.token_correction.take() {
+                    Some((model, correction)) if model == exchange.model => {
+                        correction + CORRECTION_SMOOTHING * (ratio - correction)
+                    }
+                    _ => ratio,
+                };
+                self.token_correction = Some((exchange.model.clone(), correction));
+            }
+        }
+
+        self.expire_ephemeral();
+        if exchange.ephemeral_turns != Some(0) {
+            self.conversation.push(exchange);
+        }
+        self.keep_recent()
+    }
+
+    /// Count a turn for the ephemeral pairs, removing the ones staying for no more requests.
+    fn expire_ephemeral(&mut self) {
+        self.conversation
+            .retain_mut(|exchange| match &mut exchange.ephemeral_turns {
+                Some(turns) => {
+                    *turns = turns.saturating_sub(1);
+                    *turns > 0
+                }
+                None => true,
+            });
+    }
+
+    /// New request-response pair of the current speaker with the tokenizer estimate.
+    pub fn exchange(&self, request: String, response: String) -> Exchange {
+        let estimated_tokens = self.tokenizer.as_ref().map(|tokenizer| {
+            tokenizer.encode_with_special_tokens(&request).len()
+                + tokenizer.encode_with_special_tokens(&response).len()
+        });
+
+        Exchange {
+            request,
+            response,
+            speaker: self.speaker.clone(),
+            estimated_tokens,
+            reported_tokens: None,
+            timestamp: None,
+            completion_id: None,
+            model: None,
+            ephemeral_turns: self.ephemeral_turns,
+            feedback: None,
+        }
+    }
+
+    /// Request-response pairs in the context, oldest first.
+    pub fn exchanges(&self) -> &[Exchange] {
+        &self.conversation
+    }
+
+    /// Attach the `feedback` to the most recent request-response pair, replacing the previous
+    /// one. Returns the pair, or `None` if the conversation is empty.
+    pub fn set_feedback(&mut self, feedback: Feedback) -> Option<&Exchange> {
+        let exchange = self.conversation.last_mut()?;
+        exchange.feedback = Some(feedback);
+
+        Some(exchange)
+    }
+
+    /// Remove the most recent request-response pair.
+    pub fn pop(&mut self) -> Option<Exchange> {
+        self.conversation.pop()
+    }
+
+    /// Save the request-response pairs as snapshot `name`, replacing the snapshot with
+    /// the same name. Ephemeral pairs are left out.
+    pub fn snapshot(&mut self, name: String) {
+        let conversation = self
+            .conversation
+            .iter()
+            .filter(|exchange| !exchange.is_ephemeral())
+            .cloned()
+            .collect();
+
+        self.snapshots.retain(|(snapshot, _)| *snapshot != name);
+        self.snapshots.push((name, conversation));
+    }
+
+    /// Replace the request-response pairs with the ones of snapshot `name`. The snapshot is kept,
+    /// so it can be restored again.
+    ///
+    /// Returns `false` if there is no such snapshot.
+    pub fn restore(&mut self, name: &str) -> bool {
+        let Some((_, snapshot)) = self.snapshots.iter().find(|(snapshot, _)| snapshot == name)
+        else {
+            return false;
+        };
+
+        self.conversation = snapshot.clone();
+
+        true
+    }
+
+    /// Names of the snapshots, oldest first.
+    pub fn snapshots(&self) -> impl Iterator<Item = &str> {
+        self.snapshots.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Token counts of the request-response pairs, oldest first. The counts reported by
+    /// the provider are used if known, otherwise the tokenizer estimates.
+    ///
+    /// Returns `None` if the context has no tokenizer, i.e., is not truncated.
+    pub fn exchange_tokens(&self) -> Option<Vec<usize>> {
+        self.tokenizer.as_ref()?;
+
+        Some(
+            self.conversation
+                .iter()
+                .map(|exchange| {
+                    exchange.reported_tokens.unwrap_or_else(|| {
+                        self.corrected(exchange.estimated_tokens.unwrap_or_default())
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Corrected estimate of the system message tokens, zero without tokenizer.
+    fn system_tokens(&self) -> usize {
+        match (&self.tokenizer, &self.system_message) {
+            (Some(tokenizer), Some(system_message)) => {
+                self.corrected(tokenizer.encode_with_special_tokens(system_message).len())
+            }
+            _ => 0,
+        }
+    }
+
+    /// Ratio of the provider-reported tokens to the tokenizer estimates for the model of
+    /// the latest reported pair, 1.0 until the provider reports the usage of a pair.
+    pub fn token_correction(&self) -> f64 {
+        self.token_correction
+            .as_ref()
+            .map_or(1.0, |(_, correction)| *correction)
+    }
+
+    /// Tokenizer estimate adjusted by the correction factor.
+    pub fn corrected(&self, tokens: usize) -> usize {
+        (tokens as f64 * self.token_correction()).round() as usize
+    }
+
+    /// Tokenizer used for truncating the context, if any.
+    pub fn tokenizer(&self) -> Option<&tiktoken_rs::CoreBPE> {
+        self.tokenizer.as_deref()
+    }
+
+    /// Tokens in the context split by role, counted with `tokenizer`.
+    pub fn tokens_with(&self, tokenizer: &tiktoken_rs::CoreBPE) -> TokenBreakdown {
+        let num_tokens = |m: &String| tokenizer.encode_with_special_tokens(m).len();
+
+        TokenBreakdown {
+            system: self.system_message.iter().map(num_tokens).sum(),
+            user: self
+                .conversation
+                .iter()
+                .map(|e| num_tokens(&e.request))
+                .sum(),
+            assistant: self
+                .conversation
+                .iter()
+                .map(|e| num_tokens(&e.response))
+                .sum(),
+        }
+    }
+
+    /// Dry run of the context truncation: indices of the request-response pairs that will be
+    /// discarded once a pair of `next_exchange_tokens` is pushed.
+    pub fn eviction_dry_run(&self, next_exchange_tokens: usize) -> Vec<usize> {
+        let Some(mut tokens) = self.exchange_tokens() else {
+            return Vec::new();
+        };
+        tokens.push(next_exchange_tokens);
+
+        let mut evicted = self.eviction(&tokens);
+        evicted.retain(|index| *index < self.conversation.len());
+
+        evicted
     }
 
-    /// Discard old records to keep the context within the limits.
-    fn keep_recent(&mut self) {
-        let Some(ref tokenizer) = self.tokenizer else {
-            return;
+    /// Discard old records to keep the context within the limits, returning their number.
+    fn keep_recent(&mut self) -> usize {
+        let Some(tokens) = self.exchange_tokens() else {
+            return 0;
         };
 
+        let evicted = self.eviction(&tokens);
+        for index in evicted.iter().rev() {
+            self.conversation.remove(*index);
+        }
+
+        evicted.len()
+    }
+
+    /// Indices of the request-response pairs to discard given their token counts, ascending.
+    fn eviction(&self, exchange_tokens: &[usize]) -> Vec<usize> {
+        let mut kept = (0..exchange_tokens.len()).collect::<Vec<_>>();
+        let kept_tokens =
+            |kept: &[usize]| kept.iter().map(|i| exchange_tokens[*i]).collect::<Vec<_>>();
+        let mut evicted = Vec::new();
+
+        if self.strategy == ContextStrategy::Smart {
+            while self.num_to_keep(&kept_tokens(&kept)) < kept.len() {
+                let Some(position) = self.lowest_value(&kept) else {
+                    break;
+                };
+                evicted.push(kept.remove(position));
+            }
+        }
+
+        let discard = kept.len() - self.num_to_keep(&kept_tokens(&kept));
+        evicted.extend(kept.drain(0..discard));
+        evicted.sort_unstable();
+
+        evicted
+    }
+
+    /// Position in `kept` of the oldest low-value request-response pair: a repeated request,
+    /// or, if there are none, an acknowledgement. The latest pair is never selected.
+    ///
+    /// `kept` may contain one index past the end of the conversation for the next pair.
+    fn lowest_value(&self, kept: &[usize]) -> Option<usize> {
+        let (_, older) = kept.split_last()?;
+        let request = |index: &usize| {
+            self.conversation
+                .get(*index)
+                .map(|exchange| exchange.request.trim().to_lowercase())
+        };
+
+        let mut later_requests = kept
+            .last()
+            .and_then(request)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let repeated = older
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(position, index)| {
+                let request = request(index)?;
+                (!later_requests.insert(request)).then_some(position)
+            })
+            .last();
+
+        repeated.or_else(|| {
+            let tokenizer = self.tokenizer.as_ref()?;
+
+            older.iter().position(|index| {
+                self.conversation.get(*index).is_some_and(|exchange| {
+                    tokenizer
+                        .encode_with_special_tokens(&exchange.request)
+                        .len()
+                        <= ACKNOWLEDGEMENT_MAX_TOKENS
+                })
+            })
+        })
+    }
+
+    /// Number of the most recent request-response pairs to keep given their token counts.
+    fn num_to_keep(&self, exchange_tokens: &[usize]) -> usize {
+        if self.tokenizer.is_none() {
+            return exchange_tokens.len();
+        }
+
         // At least one of the numbers is limited if tokenizer is set.
         debug_assert!(self.min_history_tokens.is_some() || self.max_history_tokens.is_some());
         let min_tokens = self.min_history_tokens.unwrap_or(usize::MAX);
         let max_tokens = self.max_history_tokens.unwrap_or(usize::MAX);
 
-        let num_tokens = |m| tokenizer.encode_with_special_tokens(m).len();
-
-        let system_tokens = self
-            .system_message
-            .as_ref()
-            .map(|m| num_tokens(m))
-            .unwrap_or_default();
+        let system_tokens = self.system_tokens();
 
-        let keep = self
-            .conversation
+        exchange_tokens
             .iter()
             .rev()
-            .map(|transaction| num_tokens(&transaction.0) + num_tokens(&transaction.1))
             .accumulate((0, system_tokens), |(_, acc), x| (acc, acc + x))
             .map_while(|(prev, current)| (prev < min_tokens).then_some(current))
             .take_while(|current| *current <= max_tokens)
-            .count();
+            .count()
+    }
+}
+
+/// Builder of an [`InitialContext`], e.g., few-shot examples for a conversation.
+///
+/// User and assistant messages must alternate, starting with a user message.
+#[derive(Debug, Default, Clone)]
+pub struct ContextBuilder {
+    system_message: Option<String>,
+    messages: Vec<(Role, String)>,
+}
 
-        let discard = self.conversation.len() - keep;
-        self.conversation.drain(0..discard);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    User,
+    Assistant,
+}
+
+impl ContextBuilder {
+    /// Create a new empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the system message.
+    pub fn system(mut self, message: impl Into<String>) -> Self {
+        self.system_message = Some(message.into());
+        self
+    }
+
+    /// Add a user message.
+    pub fn user(mut self, message: impl Into<String>) -> Self {
+        self.messages.push((Role::User, message.into()));
+        self
+    }
+
+    /// Add an assistant message answering the preceding user message.
+    pub fn assistant(mut self, message: impl Into<String>) -> Self {
+        self.messages.push((Role::Assistant, message.into()));
+        self
     }
+
+    /// Check the messages and count the tokens in the context.
+    pub fn build(self) -> Result<InitialContext, Error> {
+        let mut conversation = Vec::with_capacity(self.messages.len() / 2);
+        let mut messages = self.messages.into_iter();
+
+        while let Some((role, message)) = messages.next() {
+            if role != Role::User {
+                return Err(Error::UnexpectedAssistantMessage(message));
+            }
+            match messages.next() {
+                Some((Role::Assistant, response)) => conversation.push((message, response)),
+                _ => return Err(Error::UnansweredUserMessage(message)),
+            }
+        }
+
+        let tokenizer = SharedResources::global()
+            .chat_tokenizer()
+            .map_err(|e| Error::TokenizerInit(e.to_string()))?;
+        let num_tokens = |m: &String| tokenizer.encode_with_special_tokens(m).len();
+
+        let tokens = self.system_message.iter().map(num_tokens).sum::<usize>()
+            + conversation
+                .iter()
+                .map(|(request, response)| num_tokens(request) + num_tokens(response))
+                .sum::<usize>();
+
+        Ok(InitialContext {
+            system_message: self.system_message,
+            conversation,
+            tokens,
+        })
+    }
+}
+
+/// Messages to start a conversation with. Built with [`ContextBuilder`].
+#[derive(Debug, Clone)]
+pub struct InitialContext {
+    system_message: Option<String>,
+    conversation: Vec<(String, String)>,
+    tokens: usize,
+}
+
+impl InitialContext {
+    /// System message.
+    pub fn system_message(&self) -> Option<&str> {
+        self.system_message.as_deref()
+    }
+
+    /// Request-response pairs, oldest first.
+    pub fn exchanges(&self) -> &[(String, String)] {
+        &self.conversation
+    }
+
+    /// Tokens in the system message and all the request-response pairs.
+    pub fn tokens(&self) -> usize {
+        self.tokens
+    }
+
+    /// Split into the system message and request-response pairs.
+    pub(crate) fn into_parts(self) -> (Option<String>, Vec<(String, String)>) {
+        (self.system_message, self.conversation)
+    }
+}
+
+/// Errors when building an [`InitialContext`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Assistant message doesn't follow a user message.
+    #[error("Assistant message doesn't follow a user message: \"{0}\"")]
+    UnexpectedAssistantMessage(String),
+    /// User message is not followed by an assistant message.
+    #[error("User message has no assistant response: \"{0}\"")]
+    UnansweredUserMessage(String),
+    /// Tokenizer initialization error.
+    #[error("Failed to initialize tokenizer: {0}")]
+    TokenizerInit(String),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chat_client::openai_api::message::GenericMessage;
 
     #[test]
     fn empty() {
@@ -188,6 +812,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn conversation_without_system_message() {
+        let mut context = Context::new(Some(String::from("system")));
+        context.push(String::from("req1"), String::from("resp1"));
+
+        assert_eq!(
+            context
+                .conversation_with_request(String::from("req2"))
+                .collect::<Vec<_>>(),
+            vec![
+                UserMessage::new(String::from("req1")).into(),
+                AssistantMessage::new(String::from("resp1")).into(),
+                UserMessage::new(String::from("req2")).into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn context_builder() {
+        let initial_context = ContextBuilder::new()
+            .system("to to to to to")
+            .user("do do do do do")
+            .assistant("be be be be be")
+            .build()
+            .unwrap();
+
+        assert_eq!(initial_context.system_message(), Some("to to to to to"));
+        assert_eq!(
+            initial_context.exchanges(),
+            &[(
+                String::from("do do do do do"),
+                String::from("be be be be be")
+            )],
+        );
+        assert_eq!(initial_context.tokens(), 15);
+    }
+
+    #[test]
+    fn context_builder_unpaired_messages() {
+        assert!(matches!(
+            ContextBuilder::new().user("req").build(),
+            Err(Error::UnansweredUserMessage(m)) if m == "req",
+        ));
+        assert!(matches!(
+            ContextBuilder::new().user("req1").user("req2").assistant("resp").build(),
+            Err(Error::UnansweredUserMessage(m)) if m == "req1",
+        ));
+        assert!(matches!(
+            ContextBuilder::new().assistant("resp").build(),
+            Err(Error::UnexpectedAssistantMessage(m)) if m == "resp",
+        ));
+    }
+
     #[test]
     fn min_history_tokens() {
         let tokenizer = tiktoken_rs::o200k_base().unwrap();
@@ -277,6 +954,298 @@ mod tests {
         assert_eq!(context.conversation.len(), 2);
     }
 
+    #[test]
+    fn eviction_dry_run() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let request = "do do do do do".to_string();
+        let response = "be be be be be".to_string();
+
+        let mut context = Context::new_with_rolling_window(None, tokenizer.clone(), None, Some(30));
+
+        // 20 tokens
+        context.push(request.clone(), response.clone());
+        context.push(request.clone(), response.clone());
+        assert_eq!(context.exchange_tokens(), Some(vec![10, 10]));
+
+        // Fits into the limit
+        assert_eq!(context.eviction_dry_run(10), Vec::<usize>::new());
+
+        // Either of the old pairs must go
+        assert_eq!(context.eviction_dry_run(11), vec![0]);
+        assert_eq!(context.eviction_dry_run(20), vec![0]);
+
+        // The new pair doesn't fit, everything is discarded
+        assert_eq!(context.eviction_dry_run(31), vec![0, 1]);
+
+        // Dry run doesn't modify the context
+        assert_eq!(context.conversation.len(), 2);
+    }
+
+    #[test]
+    fn exchange_feedback() {
+        let mut context = Context::new(None);
+        let feedback = Feedback {
+            rating: Rating::Bad,
+            comment: Some(String::from("Too long")),
+        };
+        assert!(context.set_feedback(feedback.clone()).is_none());
+
+        context.push(String::from("Hi"), String::from("Hello!"));
+        context.push(String::from("What is Rust?"), String::from("A language."));
+        context.set_feedback(feedback.clone());
+
+        assert_eq!(context.exchanges()[0].feedback(), None);
+        assert_eq!(context.exchanges()[1].feedback(), Some(&feedback));
+    }
+
+    #[test]
+    fn ephemeral_exchanges() {
+        let mut context = Context::new(None);
+        let requests = |context: &Context| {
+            context
+                .exchanges()
+                .iter()
+                .map(|exchange| exchange.request().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        context.push("kept".into(), "ok".into());
+        context.set_ephemeral(Some(2));
+        context.push("secret".into(), "ok".into());
+        context.set_ephemeral(Some(0));
+        context.push("forgotten".into(), "ok".into());
+        context.set_ephemeral(None);
+        assert_eq!(requests(&context), ["kept", "secret"]);
+        assert!(context.exchanges()[1].is_ephemeral());
+
+        context.push("next".into(), "ok".into());
+        assert_eq!(requests(&context), ["kept", "next"]);
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut context = Context::new(None);
+        context.push(String::from("req1"), String::from("resp1"));
+        context.snapshot(String::from("start"));
+        context.push(String::from("req2"), String::from("resp2"));
+        context.snapshot(String::from("both"));
+
+        assert!(context.restore("start"));
+        assert_eq!(context.exchanges().len(), 1);
+        assert!(context.restore("both"));
+        assert_eq!(context.exchanges().len(), 2);
+        assert!(!context.restore("unknown"));
+        assert_eq!(context.exchanges().len(), 2);
+
+        // Snapshot with the same name is replaced and becomes the newest.
+        context.restore("start");
+        context.snapshot(String::from("both"));
+        assert_eq!(
+            context.snapshots().collect::<Vec<_>>(),
+            vec!["start", "both"]
+        );
+        assert!(context.restore("both"));
+        assert_eq!(context.exchanges().len(), 1);
+
+        assert_eq!(context.pop().unwrap().request(), "req1");
+        assert_eq!(context.pop(), None);
+    }
+
+    #[test]
+    fn speakers() {
+        let mut context = Context::new(None);
+        context.set_speaker(Some(String::from("alice")));
+        let exchange = context
+            .exchange(String::from("Hi!"), String::from("Hello, Alice!"))
+            .with_completion(String::new(), String::from("gpt-4o-2024-08-06"));
+        context.push_exchange(exchange, None);
+        context.set_speaker(None);
+
+        let exchange = &context.exchanges()[0];
+        assert_eq!(exchange.speaker(), Some("alice"));
+        assert_eq!(exchange.completion_id(), None);
+        assert_eq!(exchange.model(), Some("gpt-4o-2024-08-06"));
+        assert!(exchange.timestamp().is_some());
+
+        assert_eq!(
+            context
+                .with_request(String::from("Who said hi?"))
+                .map(GenericMessage::from)
+                .map(|message| serde_json::to_value(message).unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                serde_json::json!({ "role": "user", "content": "Hi!", "name": "alice" }),
+                serde_json::json!({ "role": "assistant", "content": "Hello, Alice!" }),
+                serde_json::json!({ "role": "user", "content": "Who said hi?" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reported_usage() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let system = "to to to to to".to_string();
+        let request = "do do do do do".to_string();
+        let response = "be be be be be".to_string();
+
+        let mut context =
+            Context::new_with_rolling_window(Some(system), tokenizer.clone(), None, Some(40));
+
+        // Prompt of 5 system and 8 request tokens as counted by the provider.
+        let exchange = context.exchange(request.clone(), response.clone());
+        context.push_exchange(exchange, Some((13, 6)));
+        assert_eq!(context.exchange_tokens(), Some(vec![14]));
+        assert_eq!(context.token_correction(), 1.4);
+
+        // Prompt with 7 corrected system tokens and the previous pair, the request counted as
+        // 5 tokens.
+        let exchange = context.exchange(request.clone(), response.clone());
+        context.push_exchange(exchange, Some((26, 6)));
+        assert_eq!(context.exchange_tokens(), Some(vec![14, 11]));
+        assert_eq!(context.exchanges()[1].tokens(), Some(11));
+        assert_eq!(context.token_correction(), 1.325);
+
+        // Corrected estimates: 7 + 14 + 11 + 13 = 45 tokens, one pair discarded.
+        assert_eq!(context.push(request.clone(), response.clone()), 1);
+        assert_eq!(context.exchange_tokens(), Some(vec![11, 13]));
+        assert_eq!(context.exchanges()[1].tokens(), Some(10));
+
+        // Implausible usage falls back to the estimate and doesn't affect the correction.
+        let exchange = context.exchange(request, response);
+        assert_eq!(context.push_exchange(exchange, Some((3, 6))), 1);
+        assert_eq!(context.exchange_tokens(), Some(vec![13, 13]));
+        assert_eq!(context.token_correction(), 1.325);
+    }
+
+    #[test]
+    fn token_correction_per_model() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let request = "do do do do do".to_string();
+        let response = "be be be be be".to_string();
+        let mut context = Context::new_with_rolling_window(None, tokenizer, None, Some(1000));
+        let exchange = |context: &Context, model: &str| {
+            context
+                .exchange(request.clone(), response.clone())
+                .with_completion(String::from("id"), String::from(model))
+        };
+
+        // 40 reported tokens for the estimated 10 are clamped to the upper bound.
+        context.push_exchange(exchange(&context, "a"), Some((30, 10)));
+        assert_eq!(context.token_correction(), 2.0);
+
+        // 2 reported tokens are clamped to the lower bound and smoothed.
+        context.push_exchange(exchange(&context, "a"), Some((41, 1)));
+        assert_eq!(context.token_correction(), 1.625);
+
+        // Another model starts over.
+        context.push_exchange(exchange(&context, "b"), Some((48, 6)));
+        assert_eq!(context.token_correction(), 1.2);
+    }
+
+    #[test]
+    fn token_breakdown() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let num_tokens = |m| tokenizer.encode_with_special_tokens(m).len();
+
+        let mut context = Context::new(Some(String::from("You are a helpful assistant.")));
+        context.push(
+            String::from("Hello there!"),
+            String::from("Hi! How can I help?"),
+        );
+        context.push(String::from("Tell a joke"), String::from("No."));
+
+        let breakdown = context.tokens_with(&tokenizer);
+        assert_eq!(
+            breakdown,
+            TokenBreakdown {
+                system: num_tokens("You are a helpful assistant."),
+                user: num_tokens("Hello there!") + num_tokens("Tell a joke"),
+                assistant: num_tokens("Hi! How can I help?") + num_tokens("No."),
+            }
+        );
+        assert_eq!(
+            breakdown.total(),
+            breakdown.system + breakdown.user + breakdown.assistant
+        );
+    }
+
+    #[test]
+    fn eviction_dry_run_without_tokenizer() {
+        let mut context = Context::default();
+        context.push(String::from("req1"), String::from("resp1"));
+
+        assert_eq!(context.exchange_tokens(), None);
+        assert!(context.eviction_dry_run(usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn smart_strategy_discards_repeated_requests() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let question = "do do do do do do".to_string();
+        let other_question = "go go go go go go".to_string();
+        let response = "be be be be be be".to_string();
+
+        let mut context = Context::new_with_rolling_window(None, tokenizer, None, Some(36))
+            .with_strategy(ContextStrategy::Smart);
+
+        // 12 tokens each
+        context.push(question.clone(), response.clone());
+        context.push(other_question.clone(), response.clone());
+        context.push(question.clone(), response.clone());
+        assert_eq!(context.conversation.len(), 3);
+
+        // The first question was repeated, it is discarded instead of the oldest pair
+        context.push(other_question.clone(), response.clone());
+        assert_eq!(
+            context
+                .exchanges()
+                .iter()
+                .map(|e| (e.request.clone(), e.response.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (other_question.clone(), response.clone()),
+                (question.clone(), response.clone()),
+                (other_question.clone(), response.clone()),
+            ],
+        );
+    }
+
+    #[test]
+    fn smart_strategy_discards_acknowledgements() {
+        let tokenizer = tiktoken_rs::o200k_base().unwrap();
+        let num_tokens = |m| tokenizer.encode_with_special_tokens(m).len();
+        let request = "do do do do do do".to_string();
+        let thanks = "thanks!".to_string();
+        let response = "be be be be be be".to_string();
+        assert_eq!(num_tokens(&request), 6);
+        assert_eq!(num_tokens(&thanks), 2);
+
+        let mut context = Context::new_with_rolling_window(None, tokenizer.clone(), None, Some(32))
+            .with_strategy(ContextStrategy::Smart);
+
+        context.push(String::from("a a a a a a"), response.clone());
+        context.push(thanks.clone(), response.clone());
+        context.push(request.clone(), response.clone());
+        assert_eq!(context.eviction_dry_run(6), vec![1]);
+
+        assert_eq!(
+            context.push(String::from("go"), String::from("be be be be")),
+            1
+        );
+        assert_eq!(
+            context
+                .exchanges()
+                .iter()
+                .map(|e| (e.request.clone(), e.response.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (String::from("a a a a a a"), response.clone()),
+                (request.clone(), response.clone()),
+                (String::from("go"), String::from("be be be be")),
+            ],
+        );
+    }
+
     #[test]
     fn max_history_tokens_exact() {
         let tokenizer = tiktoken_rs::o200k_base().unwrap();
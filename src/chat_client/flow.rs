@@ -0,0 +1,348 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Guided conversation flows: scripted sequences of questions to the user and prompts to
+//! the model with branching on the answers.
+
+use crate::chat_client::client::{self, ChatClient};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Step ID ending the flow when used as a branch target or `next`.
+pub const END: &str = "end";
+
+/// Max steps taken in a flow run, guarding against endless loops.
+const MAX_STEPS: usize = 100;
+
+/// Flow error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The flow has no steps.
+    #[error("Flow has no steps")]
+    NoSteps,
+    /// Several steps have the same ID.
+    #[error("Duplicate step `{0}`")]
+    DuplicateStep(String),
+    /// A step uses the reserved ID [`END`].
+    #[error("Step ID `end` is reserved for the end of the flow")]
+    ReservedStepId,
+    /// A step has neither or both of `ask` and `prompt`.
+    #[error("Step `{0}` must have either `ask` or `prompt`")]
+    InvalidStep(String),
+    /// A step continues with a step that doesn't exist.
+    #[error("Step `{step}` continues with unknown step `{target}`")]
+    UnknownStep {
+        /// Step ID.
+        step: String,
+        /// Missing target step ID.
+        target: String,
+    },
+    /// The run exceeded the max number of steps, likely looping.
+    #[error("Flow exceeded {0} steps")]
+    TooManySteps(usize),
+    /// The JSON flow definition could not be parsed.
+    #[error("Invalid flow definition: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// Request to the model failed.
+    #[error("Chat error: {0}")]
+    Chat(#[from] client::Error),
+}
+
+/// Step of a flow.
+///
+/// The questions and the prompts can reference the answers to the previous steps with
+/// `{id}` placeholders. Placeholders of steps not answered yet are kept as is.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Step ID.
+    pub id: String,
+    /// Question to the user, answered with [`FlowUser::answer`].
+    #[serde(default)]
+    pub ask: Option<String>,
+    /// Prompt to the model, answered with the response. The prompt and the response extend
+    /// the conversation context, so the later prompts see them.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Branches on the answer, the first matching one is taken.
+    #[serde(default)]
+    pub branches: Vec<Branch>,
+    /// Step to continue with if no branch matches. Default: the following step, or the end
+    /// of the flow after the last one.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// Branch on the answer to a step.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Branch {
+    /// Text the answer must contain, case-insensitive.
+    pub contains: String,
+    /// Step to continue with, or [`END`].
+    pub goto: String,
+}
+
+/// User side of a flow run.
+pub trait FlowUser {
+    /// Answer of the user to the `question`, `None` to stop the flow.
+    fn answer(&mut self, question: &str) -> Option<String>;
+
+    /// Model `response` to the prompt of step `id`.
+    fn response(&mut self, _id: &str, _response: &str) {}
+}
+
+/// Validated flow, deserialized from a definition with the list of `steps`, e.g., in TOML:
+///
+/// ```toml
+/// [[steps]]
+/// id = "name"
+/// ask = "What is your name?"
+///
+/// [[steps]]
+/// id = "greeting"
+/// prompt = "Greet {name} in one sentence."
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "FlowDefinition")]
+pub struct Flow {
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlowDefinition {
+    steps: Vec<Step>,
+}
+
+impl TryFrom<FlowDefinition> for Flow {
+    type Error = Error;
+
+    fn try_from(definition: FlowDefinition) -> Result<Self, Error> {
+        Flow::new(definition.steps)
+    }
+}
+
+impl Flow {
+    /// Flow of the `steps`, starting with the first one.
+    pub fn new(steps: Vec<Step>) -> Result<Self, Error> {
+        if steps.is_empty() {
+            return Err(Error::NoSteps);
+        }
+
+        let mut ids = HashSet::new();
+        for step in &steps {
+            if step.id == END {
+                return Err(Error::ReservedStepId);
+            }
+            if !ids.insert(step.id.as_str()) {
+                return Err(Error::DuplicateStep(step.id.clone()));
+            }
+            if step.ask.is_some() == step.prompt.is_some() {
+                return Err(Error::InvalidStep(step.id.clone()));
+            }
+        }
+
+        for step in &steps {
+            let targets = step.branches.iter().map(|branch| &branch.goto);
+            if let Some(target) = targets
+                .chain(&step.next)
+                .find(|target| *target != END && !ids.contains(target.as_str()))
+            {
+                return Err(Error::UnknownStep {
+                    step: step.id.clone(),
+                    target: target.clone(),
+                });
+            }
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// Flow from the JSON definition.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Steps of the flow.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Run the flow, asking the questions to the `user` and sending the prompts to the model
+    /// with `chat`.
+    ///
+    /// Returns the step IDs with the answers in the order the steps were taken, the answers
+    /// collected so far if the user stopped the flow.
+    pub async fn run(
+        &self,
+        chat: &mut ChatClient,
+        user: &mut impl FlowUser,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let mut answers = Vec::<(String, String)>::new();
+        let mut current = Some(0);
+
+        while let Some(index) = current {
+            if answers.len() == MAX_STEPS {
+                return Err(Error::TooManySteps(MAX_STEPS));
+            }
+
+            let step = &self.steps[index];
+            let latest = answers
+                .iter()
+                .map(|(id, answer)| (id.as_str(), answer.as_str()))
+                .collect::<HashMap<_, _>>();
+
+            let answer = match (&step.ask, &step.prompt) {
+                (Some(question), _) => match user.answer(&fill(question, &latest)) {
+                    Some(answer) => answer,
+                    None => break,
+                },
+                (None, Some(prompt)) => {
+                    let response = chat.ask(fill(prompt, &latest)).await?;
+                    user.response(&step.id, &response);
+                    response
+                }
+                (None, None) => unreachable!("steps are validated"),
+            };
+
+            current = self.next(index, &answer);
+            answers.push((step.id.clone(), answer));
+        }
+
+        Ok(answers)
+    }
+
+    /// Index of the step following the step `index` answered with `answer`, `None` at the end.
+    fn next(&self, index: usize, answer: &str) -> Option<usize> {
+        let step = &self.steps[index];
+        let answer = answer.to_lowercase();
+
+        let target = step
+            .branches
+            .iter()
+            .find(|branch| answer.contains(&branch.contains.to_lowercase()))
+            .map(|branch| &branch.goto)
+            .or(step.next.as_ref());
+
+        match target {
+            Some(target) if target == END => None,
+            Some(target) => self.steps.iter().position(|step| step.id == *target),
+            None => (index + 1 < self.steps.len()).then_some(index + 1),
+        }
+    }
+}
+
+/// Replace the `{id}` placeholders of the answered steps with the answers.
+fn fill(template: &str, answers: &HashMap<&str, &str>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        match after
+            .find('}')
+            .and_then(|end| answers.get(&after[..end]).map(|answer| (end, answer)))
+        {
+            Some((end, answer)) => {
+                filled.push_str(answer);
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branching() {
+        let flow = Flow::from_json(
+            r#"{"steps": [
+                {"id": "name", "ask": "Name?"},
+                {"id": "level", "prompt": "Is {name} a beginner or an expert?",
+                 "branches": [{"contains": "Expert", "goto": "end"}]},
+                {"id": "tutorial", "ask": "Want a tutorial, {name}?", "next": "level"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(flow.next(0, "Alice"), Some(1));
+        assert_eq!(flow.next(1, "An expert."), None);
+        assert_eq!(flow.next(1, "A beginner."), Some(2));
+        assert_eq!(flow.next(2, "yes"), Some(1));
+
+        let answers = HashMap::from([("name", "Alice")]);
+        assert_eq!(
+            fill("{name} {level} {}{name}", &answers),
+            "Alice {level} {}Alice"
+        );
+    }
+
+    #[test]
+    fn invalid_flows() {
+        assert!(matches!(
+            Flow::from_json(r#"{"steps": []}"#),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(Flow::new(Vec::new()), Err(Error::NoSteps)));
+
+        let step = Step {
+            id: String::from("name"),
+            ask: Some(String::from("Name?")),
+            prompt: None,
+            branches: Vec::new(),
+            next: Some(String::from("greeting")),
+        };
+        assert!(matches!(
+            Flow::new(vec![step.clone()]),
+            Err(Error::UnknownStep { target, .. }) if target == "greeting"
+        ));
+        assert!(matches!(
+            Flow::new(vec![Step {
+                prompt: Some(String::from("Hi")),
+                ..step.clone()
+            }]),
+            Err(Error::InvalidStep(_))
+        ));
+        assert!(matches!(
+            Flow::new(vec![
+                Step {
+                    next: None,
+                    ..step.clone()
+                },
+                step
+            ]),
+            Err(Error::DuplicateStep(_))
+        ));
+    }
+}
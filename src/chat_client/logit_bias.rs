@@ -0,0 +1,181 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Logit bias built from text instead of token IDs.
+
+use crate::chat_client::shared::SharedResources;
+use std::collections::{BTreeSet, HashMap};
+use tiktoken_rs::{tokenizer, CoreBPE};
+
+/// Bias banning a token.
+const BAN: i32 = -100;
+
+/// Range of the biases accepted by the API.
+const BIAS_RANGE: std::ops::RangeInclusive<i32> = -100..=100;
+
+/// Logit bias error.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Bias out of the range -100 to 100.
+    #[error("Bias {bias} of {text:?} is out of the range -100 to 100")]
+    BiasOutOfRange {
+        /// Biased text.
+        text: String,
+        /// Bias.
+        bias: i32,
+    },
+    /// Empty text to bias.
+    #[error("Text to bias is empty")]
+    EmptyText,
+    /// The tokenizer of the model is not known, so the token IDs can't be computed.
+    #[error("Tokenizer of model `{0}` is not known")]
+    UnknownTokenizer(String),
+    /// Failed to initialize the tokenizer.
+    #[error("Failed to initialize tokenizer: {0}")]
+    TokenizerInit(String),
+    /// The tokens of the text don't decode back to the text.
+    #[error("Text {0:?} is not tokenized losslessly")]
+    Lossy(String),
+}
+
+/// Biases of the likelihood of the tokens of texts, converted to the token IDs of the model
+/// once sent with [`RequestOptions::logit_bias`](crate::RequestOptions::logit_bias).
+///
+/// ```
+/// # use jutella::LogitBias;
+/// let bias = LogitBias::ban("as an AI").bias("Rust", 5);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LogitBias {
+    biases: Vec<(String, i32)>,
+}
+
+impl LogitBias {
+    /// Empty logit bias.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Logit bias banning the tokens of `text`.
+    pub fn ban(text: impl Into<String>) -> Self {
+        Self::new().bias(text, BAN)
+    }
+
+    /// Add `bias` from -100 (ban) to 100 (exclusive selection) of the tokens of `text`.
+    ///
+    /// Note that all the tokens of the text are biased, including the ones shared with
+    /// other words.
+    pub fn bias(mut self, text: impl Into<String>, bias: i32) -> Self {
+        self.biases.push((text.into(), bias));
+        self
+    }
+
+    /// Whether no biases are set.
+    pub fn is_empty(&self) -> bool {
+        self.biases.is_empty()
+    }
+
+    /// Token IDs and their biases in the `logit_bias` request format, with the tokenizer of
+    /// `model`.
+    pub(crate) fn for_model(&self, model: &str) -> Result<HashMap<String, f32>, Error> {
+        let encoding = tokenizer::get_tokenizer(model)
+            .ok_or_else(|| Error::UnknownTokenizer(model.to_string()))?;
+        let bpe = SharedResources::global()
+            .tokenizer(encoding)
+            .map_err(|e| Error::TokenizerInit(e.to_string()))?;
+
+        self.token_ids(&bpe)
+    }
+
+    /// Token IDs and their biases with the tokenizer `bpe`.
+    ///
+    /// A word is tokenized differently at the start of the text and after a space, so both
+    /// variants are biased. The tokens must decode back to the text, which fails, e.g., for
+    /// texts the tokenizer splits inside multibyte characters.
+    fn token_ids(&self, bpe: &CoreBPE) -> Result<HashMap<String, f32>, Error> {
+        let mut token_ids = HashMap::new();
+
+        for (text, bias) in &self.biases {
+            if !BIAS_RANGE.contains(bias) {
+                return Err(Error::BiasOutOfRange {
+                    text: text.clone(),
+                    bias: *bias,
+                });
+            }
+            if text.trim().is_empty() {
+                return Err(Error::EmptyText);
+            }
+
+            let variants = [
+                text.trim_start().to_string(),
+                format!(" {}", text.trim_start()),
+            ];
+            for variant in variants.into_iter().collect::<BTreeSet<_>>() {
+                let tokens = bpe.encode_ordinary(&variant);
+                if bpe.decode(tokens.clone()).ok().as_ref() != Some(&variant) {
+                    return Err(Error::Lossy(variant));
+                }
+
+                for token in tokens {
+                    token_ids.insert(token.to_string(), *bias as f32);
+                }
+            }
+        }
+
+        Ok(token_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_ids() {
+        let bpe = tiktoken_rs::o200k_base().unwrap();
+        let token_ids = LogitBias::ban("Hello").token_ids(&bpe).unwrap();
+
+        let hello = bpe.encode_ordinary("Hello");
+        let space_hello = bpe.encode_ordinary(" Hello");
+        assert_eq!((hello.len(), space_hello.len()), (1, 1));
+        assert_eq!(
+            token_ids,
+            HashMap::from([
+                (hello[0].to_string(), -100.0),
+                (space_hello[0].to_string(), -100.0),
+            ])
+        );
+
+        assert!(matches!(
+            LogitBias::new().bias("Hello", 101).token_ids(&bpe),
+            Err(Error::BiasOutOfRange { bias: 101, .. })
+        ));
+        assert!(matches!(
+            LogitBias::ban(" ").token_ids(&bpe),
+            Err(Error::EmptyText)
+        ));
+        assert!(matches!(
+            LogitBias::ban("Hello").for_model("llama3.2"),
+            Err(Error::UnknownTokenizer(_))
+        ));
+    }
+}
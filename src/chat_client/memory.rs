@@ -0,0 +1,185 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Long-term memory: user-approved facts shared across sessions.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Persistent store of facts to remember across sessions.
+///
+/// The facts are kept in a JSON file and can be injected into the system message of
+/// a new conversation with [`MemoryStore::system_message`].
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    path: PathBuf,
+    facts: Vec<String>,
+}
+
+impl MemoryStore {
+    /// Load the facts from `path`. A missing file is treated as an empty store.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+
+        let facts = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { path, facts })
+    }
+
+    /// Location of the store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remembered facts.
+    pub fn facts(&self) -> &[String] {
+        &self.facts
+    }
+
+    /// Remember a new fact and save the store.
+    pub fn remember(&mut self, fact: String) -> Result<(), Error> {
+        self.facts.push(fact);
+        self.save()
+    }
+
+    /// Forget the fact at `index` and save the store. Returns the forgotten fact.
+    pub fn forget(&mut self, index: usize) -> Result<String, Error> {
+        if index >= self.facts.len() {
+            return Err(Error::NoSuchFact(index));
+        }
+
+        let fact = self.facts.remove(index);
+        self.save()?;
+
+        Ok(fact)
+    }
+
+    /// System message extended with the remembered facts.
+    pub fn system_message(&self, system_message: Option<String>) -> Option<String> {
+        if self.facts.is_empty() {
+            return system_message;
+        }
+
+        let facts = self
+            .facts
+            .iter()
+            .map(|fact| format!("- {fact}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let facts = format!("Facts the user asked you to remember:\n{facts}");
+
+        Some(match system_message {
+            Some(system_message) => format!("{system_message}\n\n{facts}"),
+            None => facts,
+        })
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(&self.path, serde_json::to_string_pretty(&self.facts)?)?;
+
+        Ok(())
+    }
+}
+
+/// Errors when loading or saving [`MemoryStore`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Failed to read or write the store.
+    #[error("Memory store I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// The store contents are invalid.
+    #[error("Invalid memory store: {0}")]
+    Json(#[from] serde_json::Error),
+    /// No fact with such index.
+    #[error("No fact with index {0}")]
+    NoSuchFact(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("jutella-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn remember_and_forget() {
+        let path = temp_path("memories.json");
+        let _ = fs::remove_file(&path);
+
+        let mut store = MemoryStore::load(&path).unwrap();
+        assert!(store.facts().is_empty());
+
+        store.remember(String::from("fact1")).unwrap();
+        store.remember(String::from("fact2")).unwrap();
+
+        let mut store = MemoryStore::load(&path).unwrap();
+        assert_eq!(
+            store.facts(),
+            &[String::from("fact1"), String::from("fact2")]
+        );
+
+        assert_eq!(store.forget(0).unwrap(), "fact1");
+        assert!(matches!(store.forget(1), Err(Error::NoSuchFact(1))));
+
+        let store = MemoryStore::load(&path).unwrap();
+        assert_eq!(store.facts(), &[String::from("fact2")]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn system_message() {
+        let path = temp_path("memories-system-message.json");
+        let _ = fs::remove_file(&path);
+
+        let mut store = MemoryStore::load(&path).unwrap();
+        assert_eq!(store.system_message(None), None);
+        assert_eq!(
+            store.system_message(Some(String::from("system"))),
+            Some(String::from("system")),
+        );
+
+        store.remember(String::from("fact")).unwrap();
+        assert_eq!(
+            store.system_message(Some(String::from("system"))),
+            Some(String::from(
+                "system\n\nFacts the user asked you to remember:\n- fact"
+            )),
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -24,4 +24,16 @@
 
 pub mod client;
 pub mod context;
+pub mod flow;
+pub mod logit_bias;
+pub mod memory;
+#[cfg(test)]
+mod mock;
+pub mod observer;
+pub mod ollama_api;
 pub mod openai_api;
+pub mod provider;
+pub mod segments;
+pub mod shared;
+pub mod store;
+pub mod tools;
@@ -0,0 +1,217 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Read-only view of a conversation driven by a [`ChatClient`](crate::ChatClient).
+
+use crate::chat_client::context::Exchange;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+/// Capacity of the turns broadcast channel. Lagging subscribers miss the oldest turns.
+const TURNS_CAPACITY: usize = 16;
+
+/// Request-response pair added to the conversation context. Ephemeral pairs are not observed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+    /// User request.
+    pub request: String,
+    /// Assistant response.
+    pub response: String,
+}
+
+/// Tokens used by the requests of a client.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenUsage {
+    /// Input tokens.
+    pub tokens_in: usize,
+    /// Output tokens.
+    pub tokens_out: usize,
+}
+
+#[derive(Debug, Default, Clone)]
+struct State {
+    exchanges: Vec<(String, String)>,
+    usage: TokenUsage,
+}
+
+struct Shared {
+    state: RwLock<State>,
+    turns: broadcast::Sender<Turn>,
+}
+
+impl Shared {
+    fn new(state: State) -> Arc<Self> {
+        Arc::new(Self {
+            state: RwLock::new(state),
+            turns: broadcast::channel(TURNS_CAPACITY).0,
+        })
+    }
+}
+
+/// Read-only handle to the conversation of a [`ChatClient`](crate::ChatClient), obtained with
+/// [`ChatClient::observer`](crate::ChatClient::observer).
+///
+/// The handle is cheap to clone and can't send requests, e.g., for a UI showing
+/// the transcript while another task drives the conversation.
+#[derive(Clone)]
+pub struct Observer {
+    shared: Arc<Shared>,
+}
+
+impl Observer {
+    /// Request-response pairs in the conversation context, oldest first, except the ephemeral
+    /// ones.
+    pub fn exchanges(&self) -> Vec<(String, String)> {
+        self.shared
+            .state
+            .read()
+            .expect("not poisoned")
+            .exchanges
+            .clone()
+    }
+
+    /// Tokens used by the client so far.
+    pub fn usage(&self) -> TokenUsage {
+        self.shared.state.read().expect("not poisoned").usage
+    }
+
+    /// Subscribe to the turns added to the conversation context.
+    pub fn subscribe(&self) -> broadcast::Receiver<Turn> {
+        self.shared.turns.subscribe()
+    }
+}
+
+/// Client side of the observers, updated as the conversation progresses.
+///
+/// Cloning it forks the observed state, so observers of a client don't see its forks.
+pub(crate) struct Observed {
+    shared: Arc<Shared>,
+}
+
+impl Observed {
+    pub fn new(exchanges: &[Exchange]) -> Self {
+        Self {
+            shared: Shared::new(State {
+                exchanges: pairs(exchanges),
+                usage: TokenUsage::default(),
+            }),
+        }
+    }
+
+    pub fn observer(&self) -> Observer {
+        Observer {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Record the `turn` added to the context. If older pairs were discarded, the exchanges
+    /// are replaced with the current `exchanges` of the context.
+    pub fn push(&self, turn: Turn, discarded: usize, exchanges: &[Exchange]) {
+        {
+            let mut state = self.shared.state.write().expect("not poisoned");
+            if discarded > 0 {
+                state.exchanges = pairs(exchanges);
+            } else {
+                state
+                    .exchanges
+                    .push((turn.request.clone(), turn.response.clone()));
+            }
+        }
+
+        let _ = self.shared.turns.send(turn);
+    }
+
+    /// Replace the exchanges with the current `exchanges` of the context, e.g., after it was
+    /// restored from a snapshot.
+    pub fn reset(&self, exchanges: &[Exchange]) {
+        self.shared.state.write().expect("not poisoned").exchanges = pairs(exchanges);
+    }
+
+    pub fn add_usage(&self, tokens_in: usize, tokens_out: usize) {
+        let mut state = self.shared.state.write().expect("not poisoned");
+        state.usage.tokens_in += tokens_in;
+        state.usage.tokens_out += tokens_out;
+    }
+}
+
+fn pairs(exchanges: &[Exchange]) -> Vec<(String, String)> {
+    exchanges
+        .iter()
+        .filter(|exchange| !exchange.is_ephemeral())
+        .map(|exchange| {
+            (
+                exchange.request().to_string(),
+                exchange.response().to_string(),
+            )
+        })
+        .collect()
+}
+
+impl Clone for Observed {
+    fn clone(&self) -> Self {
+        let state = self.shared.state.read().expect("not poisoned").clone();
+
+        Self {
+            shared: Shared::new(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observer_follows_client() {
+        let observed = Observed::new(&[]);
+        let observer = observed.observer();
+        let mut turns = observer.subscribe();
+
+        let turn = Turn {
+            request: String::from("Hi"),
+            response: String::from("Hello!"),
+        };
+        observed.push(turn.clone(), 0, &[]);
+        observed.add_usage(10, 2);
+
+        assert_eq!(
+            observer.exchanges(),
+            vec![(String::from("Hi"), String::from("Hello!"))]
+        );
+        assert_eq!(turns.try_recv().unwrap(), turn);
+        assert_eq!(
+            observer.usage(),
+            TokenUsage {
+                tokens_in: 10,
+                tokens_out: 2
+            }
+        );
+
+        // A fork doesn't report to the observers of the original.
+        let fork = observed.clone();
+        fork.add_usage(1, 1);
+        fork.push(turn, 1, &[]);
+        assert_eq!(observer.exchanges().len(), 1);
+        assert_eq!(observer.usage().tokens_in, 10);
+        assert!(turns.try_recv().is_err());
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Ollama native chat API (`/api/chat`), translated from and to the OpenAI types.
+
+use crate::chat_client::openai_api::{
+    chat_completions::{
+        ChatCompletions, ChatCompletionsBody, CompletionChoice, ResponseFormat, Usage,
+    },
+    message::AssistantMessage,
+    models::{Model, ModelList},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, value::Value, Map};
+
+/// Chat endpoint relative to the base URL.
+pub const CHAT_ENDPOINT: &str = "api/chat";
+
+/// Local models endpoint relative to the base URL.
+pub const TAGS_ENDPOINT: &str = "api/tags";
+
+/// Ollama specific request options.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OllamaOptions {
+    /// Model options, e.g., `num_ctx`. See the `Modelfile` parameters in the Ollama docs.
+    ///
+    /// Sampling parameters set in the request, e.g., `temperature`, take precedence.
+    pub options: Map<String, Value>,
+    /// How long the model stays loaded after the request, e.g., "10m", or "-1" to keep it
+    /// loaded. The server default is used if unset.
+    pub keep_alive: Option<String>,
+}
+
+/// Ollama chat request body.
+#[derive(Debug, Serialize)]
+pub struct ChatBody {
+    model: String,
+    messages: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    options: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
+    stream: bool,
+}
+
+impl ChatBody {
+    /// Translate the chat completions request.
+    pub fn new(body: &ChatCompletionsBody, ollama: &OllamaOptions) -> Self {
+        let messages = body
+            .messages
+            .iter()
+            .map(|message| {
+                let mut message = serde_json::to_value(message).expect("message to serialize");
+                arguments_to_objects(&mut message);
+                message
+            })
+            .collect();
+
+        let format = match &body.response_format {
+            None | Some(ResponseFormat::Text) => None,
+            Some(ResponseFormat::JsonObject) => Some(json!("json")),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => Some(schema.clone()),
+        };
+
+        let mut options = ollama.options.clone();
+        let parameters = [
+            ("temperature", body.temperature.map(Value::from)),
+            ("top_p", body.top_p.map(Value::from)),
+            ("seed", body.seed.map(Value::from)),
+            ("num_predict", body.max_completion_tokens.map(Value::from)),
+            ("frequency_penalty", body.frequency_penalty.map(Value::from)),
+            ("presence_penalty", body.presence_penalty.map(Value::from)),
+            (
+                "stop",
+                (!body.stop.is_empty()).then(|| Value::from(body.stop.clone())),
+            ),
+        ];
+        for (name, value) in parameters {
+            if let Some(value) = value {
+                options.insert(name.to_string(), value);
+            }
+        }
+
+        Self {
+            model: body.model.clone(),
+            messages,
+            tools: body.tools.clone(),
+            format,
+            options,
+            keep_alive: ollama.keep_alive.clone(),
+            stream: false,
+        }
+    }
+}
+
+/// Ollama expects the tool call arguments as JSON objects instead of strings.
+fn arguments_to_objects(message: &mut Value) {
+    let Some(tool_calls) = message.get_mut("tool_calls").and_then(Value::as_array_mut) else {
+        return;
+    };
+
+    for call in tool_calls {
+        if let Some(arguments) = call.pointer_mut("/function/arguments") {
+            if let Some(parsed) = arguments
+                .as_str()
+                .and_then(|arguments| serde_json::from_str(arguments).ok())
+            {
+                *arguments = parsed;
+            }
+        }
+    }
+}
+
+/// Ollama chat response (fields not used omitted).
+#[derive(Debug, Deserialize)]
+pub struct ChatResponse {
+    model: String,
+    message: ResponseMessage,
+    done_reason: Option<String>,
+    prompt_eval_count: Option<usize>,
+    eval_count: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseMessage {
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<ResponseToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseToolCall {
+    function: ResponseFunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseFunctionCall {
+    name: String,
+    arguments: Value,
+}
+
+impl From<ChatResponse> for ChatCompletions {
+    fn from(response: ChatResponse) -> Self {
+        let ChatResponse {
+            model,
+            message,
+            done_reason,
+            prompt_eval_count,
+            eval_count,
+        } = response;
+
+        // Ollama doesn't assign IDs to the tool calls, so they are numbered.
+        let tool_calls = (!message.tool_calls.is_empty()).then(|| {
+            message
+                .tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(index, call)| {
+                    json!({
+                        "id": format!("call_{index}"),
+                        "type": "function",
+                        "function": {
+                            "name": call.function.name,
+                            "arguments": call.function.arguments.to_string(),
+                        },
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into()
+        });
+
+        let finish_reason = if tool_calls.is_some() {
+            String::from("tool_calls")
+        } else {
+            done_reason.unwrap_or_else(|| String::from("stop"))
+        };
+
+        let usage = prompt_eval_count
+            .zip(eval_count)
+            .map(|(prompt_tokens, completion_tokens)| Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                prompt_tokens_details: None,
+                completion_tokens_details: None,
+            });
+
+        ChatCompletions {
+            id: String::new(),
+            choices: vec![CompletionChoice {
+                finish_reason,
+                index: 0,
+                message: AssistantMessage {
+                    content: Some(message.content),
+                    name: None,
+                    refusal: None,
+                    tool_calls,
+                }
+                .into(),
+                logprobs: None,
+                content_filter_results: None,
+            }],
+            created: 0,
+            model,
+            service_tier: None,
+            system_fingerprint: String::new(),
+            object: String::from("chat.completion"),
+            usage,
+            prompt_filter_results: None,
+        }
+    }
+}
+
+/// Ollama local models response (fields not used omitted).
+#[derive(Debug, Deserialize)]
+pub struct Tags {
+    models: Vec<Tag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tag {
+    /// Model name with the tag, e.g., "llama3.2:latest".
+    name: String,
+}
+
+impl From<Tags> for ModelList {
+    fn from(tags: Tags) -> Self {
+        ModelList {
+            data: tags
+                .models
+                .into_iter()
+                .map(|tag| Model { id: tag.name })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_client::openai_api::message::{Message, UserMessage};
+
+    #[test]
+    fn request_translation() {
+        let body = ChatCompletionsBody {
+            model: String::from("llama3.2"),
+            messages: vec![
+                Message::from(UserMessage::new(String::from("Weather in Berlin?"))).into(),
+                Message::from(AssistantMessage {
+                    content: None,
+                    name: None,
+                    refusal: None,
+                    tool_calls: Some(json!([{
+                        "id": "call_0",
+                        "type": "function",
+                        "function": { "name": "get_weather", "arguments": "{\"city\":\"Berlin\"}" },
+                    }])),
+                })
+                .into(),
+            ],
+            temperature: Some(0.5),
+            response_format: Some(ResponseFormat::JsonObject),
+            ..Default::default()
+        };
+        let ollama = OllamaOptions {
+            options: json!({ "num_ctx": 8192, "temperature": 1.0 })
+                .as_object()
+                .unwrap()
+                .clone(),
+            keep_alive: Some(String::from("10m")),
+        };
+
+        assert_eq!(
+            serde_json::to_value(ChatBody::new(&body, &ollama)).unwrap(),
+            json!({
+                "model": "llama3.2",
+                "messages": [
+                    { "role": "user", "content": "Weather in Berlin?" },
+                    {
+                        "role": "assistant",
+                        "tool_calls": [{
+                            "id": "call_0",
+                            "type": "function",
+                            "function": { "name": "get_weather", "arguments": { "city": "Berlin" } },
+                        }],
+                    },
+                ],
+                "format": "json",
+                "options": { "num_ctx": 8192, "temperature": 0.5 },
+                "keep_alive": "10m",
+                "stream": false,
+            })
+        );
+    }
+
+    #[test]
+    fn response_translation() {
+        let response: ChatResponse = serde_json::from_value(json!({
+            "model": "llama3.2",
+            "created_at": "2024-12-12T14:13:43.416799Z",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{ "function": { "name": "get_weather", "arguments": { "city": "Berlin" } } }],
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 26,
+            "eval_count": 12,
+        }))
+        .unwrap();
+
+        let completions = ChatCompletions::from(response);
+        let choice = &completions.choices[0];
+
+        assert_eq!(choice.finish_reason, "tool_calls");
+        assert_eq!(
+            serde_json::to_value(&choice.message).unwrap()["tool_calls"],
+            json!([{
+                "id": "call_0",
+                "type": "function",
+                "function": { "name": "get_weather", "arguments": "{\"city\":\"Berlin\"}" },
+            }])
+        );
+        assert_eq!(completions.usage.unwrap().total_tokens, 38);
+    }
+}
@@ -98,6 +98,13 @@ pub struct ChatCompletionsBody {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_completion_tokens: Option<usize>,
 
+    /// Output types the model should generate, e.g., `["image", "text"]` for image generation
+    /// with OpenRouter.
+    ///
+    /// Defaults to `["text"]`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modalities: Vec<Modality>,
+
     /// How many chat completion choices to generate for each input message.Note that you will be
     /// charged based on the number of generated tokens across all of the choices.
     /// Keep `n` as `1` to minimize costs.
@@ -117,6 +124,13 @@ pub struct ChatCompletionsBody {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub presence_penalty: Option<f32>,
 
+    /// Constrains effort on reasoning for reasoning models, e.g., o1 and o3-mini. Reducing
+    /// reasoning effort can result in faster responses and fewer tokens used on reasoning.
+    ///
+    /// Defaults to `medium`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+
     /// An object specifying the format that the model must output. Compatible with GPT-4o,
     /// GPT-4o mini, GPT-4 Turbo and all GPT-3.5 Turbo models newer than `gpt-3.5-turbo-1106`.
     ///
@@ -134,7 +148,7 @@ pub struct ChatCompletionsBody {
     /// `finish_reason="length"`, which indicates the generation exceeded `max_tokens` or the
     /// conversation exceeded the max context length.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub response_format: Option<Value>,
+    pub response_format: Option<ResponseFormat>,
 
     /// This feature is in Beta. If specified, our system will make a best effort to sample
     /// deterministically, such that repeated requests with the same `seed` and parameters should
@@ -225,6 +239,319 @@ pub struct ChatCompletionsBody {
     pub user: Option<String>,
 }
 
+/// Output type of the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modality {
+    /// Text output.
+    Text,
+    /// Image output.
+    Image,
+}
+
+/// Reasoning effort of reasoning models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Low effort.
+    Low,
+    /// Medium effort.
+    Medium,
+    /// High effort.
+    High,
+}
+
+/// Sampling parameter out of its valid range.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("`{parameter}` must be between {min} and {max}, got {value}")]
+pub struct OutOfRangeError {
+    parameter: &'static str,
+    value: f32,
+    min: f32,
+    max: f32,
+}
+
+fn check_range(
+    parameter: &'static str,
+    value: f32,
+    min: f32,
+    max: f32,
+) -> Result<f32, OutOfRangeError> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(OutOfRangeError {
+            parameter,
+            value,
+            min,
+            max,
+        })
+    }
+}
+
+/// Sampling temperature, between 0 and 2.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Deserialize)]
+#[serde(try_from = "f32")]
+pub struct Temperature(f32);
+
+impl Temperature {
+    /// Temperature `value`, or an error if it's out of range.
+    pub fn new(value: f32) -> Result<Self, OutOfRangeError> {
+        check_range("temperature", value, 0.0, 2.0).map(Self)
+    }
+
+    /// Value of the temperature.
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl TryFrom<f32> for Temperature {
+    type Error = OutOfRangeError;
+
+    fn try_from(value: f32) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// Nucleus sampling probability mass, between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Deserialize)]
+#[serde(try_from = "f32")]
+pub struct TopP(f32);
+
+impl TopP {
+    /// Probability mass `value`, or an error if it's out of range.
+    pub fn new(value: f32) -> Result<Self, OutOfRangeError> {
+        check_range("top_p", value, 0.0, 1.0).map(Self)
+    }
+
+    /// Value of the probability mass.
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl TryFrom<f32> for TopP {
+    type Error = OutOfRangeError;
+
+    fn try_from(value: f32) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// Format the model must output.
+///
+/// Serialized as `{ "type": "text" }`, `{ "type": "json_object" }` or
+/// `{ "type": "json_schema", "json_schema": {...} }`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseFormat {
+    /// Plain text response. This is the default.
+    Text,
+    /// JSON mode: the model generates a valid JSON object.
+    ///
+    /// You must also instruct the model to produce JSON via a system or user message.
+    JsonObject,
+    /// Structured Outputs: the model output matches the supplied JSON schema.
+    JsonSchema {
+        /// The name of the response format. Must be a-z, A-Z, 0-9, or contain underscores
+        /// and dashes, with a maximum length of 64.
+        name: String,
+        /// A description of what the response format is for, used by the model to determine
+        /// how to respond in the format.
+        description: Option<String>,
+        /// The schema for the response format, described as a JSON Schema object.
+        schema: Value,
+        /// Whether to enable strict schema adherence when generating the output.
+        ///
+        /// Defaults to `false`.
+        strict: Option<bool>,
+    },
+}
+
+impl ResponseFormat {
+    /// Start building a [`ResponseFormat::JsonSchema`] with the given name and schema.
+    pub fn json_schema(name: impl Into<String>, schema: Value) -> JsonSchemaBuilder {
+        JsonSchemaBuilder {
+            name: name.into(),
+            description: None,
+            schema,
+            strict: None,
+        }
+    }
+
+    /// [`ResponseFormat::JsonSchema`] with the schema of `T` generated by `schemars`.
+    #[cfg(feature = "schemars")]
+    pub fn for_type<T: schemars::JsonSchema>() -> ResponseFormat {
+        const MAX_NAME_LEN: usize = 64;
+
+        let name = T::schema_name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_NAME_LEN)
+            .collect::<String>();
+
+        let mut schema = schemars::schema_for!(T);
+        schema.remove("$schema");
+
+        ResponseFormat::json_schema(name, schema.to_value()).build()
+    }
+}
+
+/// Builder for [`ResponseFormat::JsonSchema`].
+#[derive(Debug, Clone)]
+pub struct JsonSchemaBuilder {
+    name: String,
+    description: Option<String>,
+    schema: Value,
+    strict: Option<bool>,
+}
+
+impl JsonSchemaBuilder {
+    /// Describe what the response format is for.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Enable or disable strict schema adherence.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = Some(strict);
+        self
+    }
+
+    /// Build the response format.
+    pub fn build(self) -> ResponseFormat {
+        let JsonSchemaBuilder {
+            name,
+            description,
+            schema,
+            strict,
+        } = self;
+
+        ResponseFormat::JsonSchema {
+            name,
+            description,
+            schema,
+            strict,
+        }
+    }
+}
+
+impl From<JsonSchemaBuilder> for ResponseFormat {
+    fn from(builder: JsonSchemaBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Wire representation of [`ResponseFormat`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ResponseFormatRepr<'a> {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaRepr<'a> },
+}
+
+#[derive(Serialize)]
+struct JsonSchemaRepr<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    schema: &'a Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strict: Option<bool>,
+}
+
+impl Serialize for ResponseFormat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            ResponseFormat::Text => ResponseFormatRepr::Text,
+            ResponseFormat::JsonObject => ResponseFormatRepr::JsonObject,
+            ResponseFormat::JsonSchema {
+                name,
+                description,
+                schema,
+                strict,
+            } => ResponseFormatRepr::JsonSchema {
+                json_schema: JsonSchemaRepr {
+                    name,
+                    description: description.as_deref(),
+                    schema,
+                    strict: *strict,
+                },
+            },
+        };
+
+        repr.serialize(serializer)
+    }
+}
+
+/// Owned wire representation of [`ResponseFormat`] for deserialization.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ResponseFormatDef {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaDef },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonSchemaDef {
+    name: String,
+    description: Option<String>,
+    /// Schema object, or a string with the JSON of the schema.
+    schema: Value,
+    strict: Option<bool>,
+}
+
+/// Deserialized from the wire format, e.g., from a config file. The schema of
+/// `json_schema` can also be a string with JSON, and must be a JSON object.
+impl<'de> Deserialize<'de> for ResponseFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        Ok(match ResponseFormatDef::deserialize(deserializer)? {
+            ResponseFormatDef::Text => ResponseFormat::Text,
+            ResponseFormatDef::JsonObject => ResponseFormat::JsonObject,
+            ResponseFormatDef::JsonSchema {
+                json_schema:
+                    JsonSchemaDef {
+                        name,
+                        description,
+                        schema,
+                        strict,
+                    },
+            } => {
+                let schema = match schema {
+                    Value::String(json) => serde_json::from_str(&json).map_err(|e| {
+                        D::Error::custom(format!("invalid JSON schema of `{name}`: {e}"))
+                    })?,
+                    schema => schema,
+                };
+                if !schema.is_object() {
+                    return Err(D::Error::custom(format!(
+                        "JSON schema of `{name}` must be an object"
+                    )));
+                }
+
+                ResponseFormat::JsonSchema {
+                    name,
+                    description,
+                    schema,
+                    strict,
+                }
+            }
+        })
+    }
+}
+
 /// OpenAI API Chat Completions response.
 ///
 /// Represents a chat completion response returned by model, based on the provided input.
@@ -257,7 +584,7 @@ pub struct ChatCompletionsBody {
 ///   }
 /// }
 /// ```
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct ChatCompletions {
     /// A unique identifier for the chat completion.
     pub id: String,
@@ -280,17 +607,25 @@ pub struct ChatCompletions {
     ///
     /// Can be used in conjunction with the `seed` request parameter to understand when
     /// backend changes have been made that might impact determinism.
+    ///
+    /// Empty if not returned, e.g., by Mistral AI.
+    #[serde(default)]
     pub system_fingerprint: String,
 
     /// The object type, which is always `chat.completion`.
     pub object: String,
 
     /// Usage statistics for the completion request.
-    pub usage: Usage,
+    ///
+    /// Missing in responses of some OpenAI-compatible APIs.
+    pub usage: Option<Usage>,
+
+    /// Content filter annotations of the prompt. Azure specific.
+    pub prompt_filter_results: Option<Value>,
 }
 
 /// Completion choice
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct CompletionChoice {
     /// The reason the model stopped generating tokens. This will be `stop` if the model hit a
     /// natural stop point or a provided stop sequence, `length` if the maximum number of tokens
@@ -306,7 +641,52 @@ pub struct CompletionChoice {
     pub message: GenericMessage,
 
     ///  Log probability information for the choice.
-    pub logprobs: Option<Value>,
+    pub logprobs: Option<Logprobs>,
+
+    /// Content filter annotations of the choice. Azure specific.
+    pub content_filter_results: Option<Value>,
+}
+
+/// Log probability information of a choice.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Logprobs {
+    /// Log probabilities of the message content tokens.
+    pub content: Option<Vec<TokenLogprob>>,
+
+    /// Log probabilities of the message refusal tokens.
+    pub refusal: Option<Vec<TokenLogprob>>,
+}
+
+/// Log probability of a generated token.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenLogprob {
+    /// The token.
+    pub token: String,
+
+    /// The log probability of the token, -9999.0 if it is very unlikely.
+    pub logprob: f64,
+
+    /// UTF-8 bytes of the token, useful if characters span several tokens. `None` if the
+    /// token has no bytes representation.
+    pub bytes: Option<Vec<u8>>,
+
+    /// The most likely tokens at this position with their log probabilities, as many as
+    /// requested with `top_logprobs`.
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// Log probability of a likely token at a position.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TopLogprob {
+    /// The token.
+    pub token: String,
+
+    /// The log probability of the token.
+    pub logprob: f64,
+
+    /// UTF-8 bytes of the token.
+    pub bytes: Option<Vec<u8>>,
 }
 
 /// Usage details
@@ -327,3 +707,222 @@ pub struct Usage {
     /// Breakdown of tokens used in a completion.
     pub completion_tokens_details: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sampling_ranges() {
+        assert_eq!(Temperature::new(0.7).unwrap().get(), 0.7);
+        assert_eq!(
+            Temperature::new(2.5).unwrap_err().to_string(),
+            "`temperature` must be between 0 and 2, got 2.5"
+        );
+        assert!(Temperature::new(f32::NAN).is_err());
+        assert!(TopP::new(1.0).is_ok());
+        assert!(TopP::new(-0.1).is_err());
+
+        assert_eq!(
+            serde_json::from_value::<TopP>(json!(0.9)).unwrap(),
+            TopP::new(0.9).unwrap()
+        );
+        assert!(serde_json::from_value::<TopP>(json!(1.5)).is_err());
+    }
+
+    #[test]
+    fn response_format_serialization() {
+        assert_eq!(
+            serde_json::to_value(ResponseFormat::Text).unwrap(),
+            json!({ "type": "text" }),
+        );
+        assert_eq!(
+            serde_json::to_value(ResponseFormat::JsonObject).unwrap(),
+            json!({ "type": "json_object" }),
+        );
+
+        let schema = json!({
+            "type": "object",
+            "properties": { "answer": { "type": "string" } },
+        });
+        assert_eq!(
+            serde_json::to_value(ResponseFormat::from(
+                ResponseFormat::json_schema("answer", schema.clone()).strict(true)
+            ))
+            .unwrap(),
+            json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "answer",
+                    "schema": schema,
+                    "strict": true,
+                },
+            }),
+        );
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn response_format_for_type() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Answer {
+            text: String,
+        }
+
+        let ResponseFormat::JsonSchema { name, schema, .. } = ResponseFormat::for_type::<Answer>()
+        else {
+            panic!("expected JSON schema response format");
+        };
+
+        assert_eq!(name, "Answer");
+        assert_eq!(schema["properties"]["text"], json!({ "type": "string" }));
+        assert!(schema.get("$schema").is_none());
+        assert_eq!(
+            ResponseFormat::for_type::<Vec<String>>(),
+            ResponseFormat::json_schema(
+                "Array_of_string",
+                json!({
+                    "title": "Array_of_string",
+                    "type": "array",
+                    "items": { "type": "string" },
+                })
+            )
+            .build()
+        );
+    }
+
+    #[test]
+    fn response_format_deserialization() {
+        let format = ResponseFormat::json_schema("answer", json!({ "type": "object" }))
+            .strict(true)
+            .build();
+        assert_eq!(
+            serde_json::from_value::<ResponseFormat>(serde_json::to_value(&format).unwrap())
+                .unwrap(),
+            format
+        );
+        assert_eq!(
+            serde_json::from_value::<ResponseFormat>(json!({
+                "type": "json_schema",
+                "json_schema": { "name": "answer", "schema": "{\"type\": \"object\"}" },
+            }))
+            .unwrap(),
+            ResponseFormat::json_schema("answer", json!({ "type": "object" })).build()
+        );
+        assert_eq!(
+            serde_json::from_value::<ResponseFormat>(json!({ "type": "json_object" })).unwrap(),
+            ResponseFormat::JsonObject
+        );
+
+        for invalid in [
+            json!({ "type": "json_schema", "json_schema": { "name": "a", "schema": "{" } }),
+            json!({ "type": "json_schema", "json_schema": { "name": "a", "schema": [] } }),
+            json!({ "type": "yaml" }),
+        ] {
+            assert!(serde_json::from_value::<ResponseFormat>(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn logprobs() {
+        let choice: CompletionChoice = serde_json::from_value(json!({
+            "index": 0,
+            "message": { "role": "assistant", "content": "Hi" },
+            "logprobs": {
+                "content": [{
+                    "token": "Hi",
+                    "logprob": -0.25,
+                    "bytes": [72, 105],
+                    "top_logprobs": [
+                        { "token": "Hi", "logprob": -0.25, "bytes": [72, 105] },
+                        { "token": "Hello", "logprob": -1.5, "bytes": null },
+                    ],
+                }],
+                "refusal": null,
+            },
+            "finish_reason": "stop",
+        }))
+        .unwrap();
+
+        let content = choice.logprobs.unwrap().content.unwrap();
+        assert_eq!(content[0].bytes, Some(b"Hi".to_vec()));
+        assert_eq!(
+            content[0].top_logprobs[1],
+            TopLogprob {
+                token: String::from("Hello"),
+                logprob: -1.5,
+                bytes: None,
+            }
+        );
+    }
+
+    #[test]
+    fn azure_content_filter_results() {
+        let completions: ChatCompletions = serde_json::from_value(json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1677652288,
+            "model": "gpt-4o-mini",
+            "system_fingerprint": "fp_44709d6fcb",
+            "prompt_filter_results": [{
+                "prompt_index": 0,
+                "content_filter_results": { "hate": { "filtered": false, "severity": "safe" } },
+            }],
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": null },
+                "logprobs": null,
+                "finish_reason": "content_filter",
+                "content_filter_results": { "hate": { "filtered": true, "severity": "high" } },
+            }],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 0, "total_tokens": 9 },
+        }))
+        .unwrap();
+
+        assert!(completions.prompt_filter_results.is_some());
+        assert_eq!(completions.choices[0].finish_reason, "content_filter");
+        assert_eq!(
+            completions.choices[0].content_filter_results,
+            Some(json!({ "hate": { "filtered": true, "severity": "high" } })),
+        );
+    }
+
+    #[test]
+    fn openrouter_image_output() {
+        let body = ChatCompletionsBody {
+            modalities: vec![Modality::Image, Modality::Text],
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&body).unwrap()["modalities"],
+            json!(["image", "text"])
+        );
+
+        let mut completions: ChatCompletions = serde_json::from_value(json!({
+            "id": "gen-123",
+            "object": "chat.completion",
+            "created": 1756132800,
+            "model": "google/gemini-2.5-flash-image-preview",
+            "system_fingerprint": "",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": {
+                    "role": "assistant",
+                    "content": "Here is a cat.",
+                    "images": [{
+                        "type": "image_url",
+                        "image_url": { "url": "data:image/png;base64,iVBORw0KGgo=" },
+                    }],
+                },
+            }],
+        }))
+        .unwrap();
+
+        let images = completions.choices[0].message.take_images();
+        assert_eq!(images[0].url, "data:image/png;base64,iVBORw0KGgo=");
+        assert_eq!(images[0].media_type(), Some("image/png"));
+    }
+}
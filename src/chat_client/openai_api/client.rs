@@ -22,66 +22,302 @@
 
 //! OpenAI REST API client.
 
-use crate::chat_client::openai_api::chat_completions::{ChatCompletions, ChatCompletionsBody};
+use crate::chat_client::openai_api::{
+    entra_id::{EntraIdCredential, TokenProvider},
+    rate_limit::{RateLimiter, RateLimits},
+};
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue, InvalidHeaderValue, AUTHORIZATION},
-    Client, ClientBuilder, StatusCode,
+    Client, ClientBuilder, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::Value;
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{self, Display},
+    hash::{BuildHasher as _, Hasher as _},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use serde::Deserialize;
-use std::{fmt::Display, str::FromStr, time::Duration};
 
-const CHAT_COMPLETIONS_ENDPOINT: &str = "chat/completions";
+pub const CHAT_COMPLETIONS_ENDPOINT: &str = "chat/completions";
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// Time after failing over until requests are sent to the primary endpoint first again.
+const PRIMARY_RECOVERY_INTERVAL: Duration = Duration::from_secs(300);
 
 /// Authorization header.
 ///
-/// Use `HeaderMap::try_from(auth)` to convert to `reqwest` headers.
-#[derive(Debug)]
+/// Use `HeaderMap::try_from(auth)` to convert to `reqwest` headers. [`Auth::EntraId`]
+/// converts to no headers, as its tokens are obtained by the client for every request.
+#[derive(Debug, Clone)]
 pub enum Auth {
     /// Auth header `Authorization: Bearer {api_token}`.
     Token(String),
     /// Auth header `api-key: {api_key}`.
     ApiKey(String),
+    /// Auth header `Authorization: Bearer {token}` with Microsoft Entra ID (Azure AD) tokens,
+    /// obtained with the credential and refreshed before they expire.
+    EntraId(EntraIdCredential),
+    /// No auth header, e.g., for a local Ollama server.
+    None,
 }
 
 impl TryFrom<Auth> for HeaderMap {
     type Error = InvalidHeaderValue;
 
     fn try_from(auth: Auth) -> Result<Self, InvalidHeaderValue> {
-        let headers = match auth {
-            Auth::Token(token) => [(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {token}"))?,
-            )],
-            Auth::ApiKey(api_key) => [(
-                HeaderName::from_str("api-key").expect("to be valid ASCII"),
-                HeaderValue::from_str(&api_key)?,
-            )],
+        let mut headers = HeaderMap::new();
+
+        match auth {
+            Auth::Token(token) => {
+                headers.insert(
+                    AUTHORIZATION,
+                    HeaderValue::from_str(&format!("Bearer {token}"))?,
+                );
+            }
+            Auth::ApiKey(api_key) => {
+                headers.insert(
+                    HeaderName::from_str("api-key").expect("to be valid ASCII"),
+                    HeaderValue::from_str(&api_key)?,
+                );
+            }
+            Auth::EntraId(_) | Auth::None => {}
         }
-        .into_iter()
-        .collect();
 
         Ok(headers)
     }
 }
 
+/// Retries of requests failing with transport errors, HTTP 429 or 5xx.
+///
+/// Retries are delayed with exponential backoff with jitter, or as requested by the
+/// `Retry-After` / `retry-after-ms` response headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Max retries after the first attempt.
+    pub max_retries: usize,
+    /// Backoff before the first retry, doubled for every next one.
+    pub initial_backoff: Duration,
+    /// Max backoff. Requests asking to retry after a longer delay are not retried.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry number `retry` (starting from 0), between half and full
+    /// exponential backoff.
+    fn backoff(&self, retry: usize) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << retry.min(16))
+            .min(self.max_backoff);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+
+        backoff.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+/// HTTP(S) proxy of the API requests.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Proxy {
+    /// Proxy URL, e.g., "http://proxy.example.com:8080".
+    pub url: String,
+    /// Username and password of the proxy basic auth.
+    pub credentials: Option<(String, String)>,
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("url", &self.url)
+            .field(
+                "username",
+                &self.credentials.as_ref().map(|(username, _)| username),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// Custom TLS trust and client authentication, e.g., of self-hosted gateways.
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Tls {
+    /// PEM root certificates trusted in addition to the built-in ones, e.g., of a corporate
+    /// TLS interception proxy.
+    pub root_certificates: Option<Vec<u8>>,
+    /// PEM client certificate chain and private key presented for mutual TLS. The key must be
+    /// PKCS#8 with the `native-tls` feature.
+    pub client_certificate: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl fmt::Debug for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tls")
+            .field("root_certificates", &self.root_certificates.is_some())
+            .field("client_certificate", &self.client_certificate.is_some())
+            .finish()
+    }
+}
+
+impl Tls {
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    fn configure(self, mut builder: ClientBuilder) -> Result<ClientBuilder, Error> {
+        if let Some(pem) = self.root_certificates {
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| tls_error("Invalid root certificate", e))?;
+            if certificates.is_empty() {
+                return Err(Error::Tls(String::from("No root certificates in the PEM")));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some((certificate, key)) = self.client_certificate {
+            #[cfg(feature = "native-tls")]
+            let identity = reqwest::Identity::from_pkcs8_pem(&certificate, &key);
+            #[cfg(not(feature = "native-tls"))]
+            let identity = reqwest::Identity::from_pem(&[certificate, key].concat());
+
+            builder =
+                builder.identity(identity.map_err(|e| tls_error("Invalid client certificate", e))?);
+        }
+
+        Ok(builder)
+    }
+
+    #[cfg(not(any(feature = "rustls-tls", feature = "native-tls")))]
+    fn configure(self, builder: ClientBuilder) -> Result<ClientBuilder, Error> {
+        if self == Tls::default() {
+            Ok(builder)
+        } else {
+            Err(Error::Tls(String::from(
+                "Custom certificates need the `rustls-tls` or `native-tls` feature",
+            )))
+        }
+    }
+}
+
+/// Fail early on an HTTPS URL without a TLS backend, as reqwest only reports
+/// the unsupported scheme on the first request.
+pub(crate) fn ensure_tls_support(url: &str) -> Result<(), Error> {
+    if cfg!(any(feature = "rustls-tls", feature = "native-tls")) || !url.starts_with("https:") {
+        Ok(())
+    } else {
+        Err(Error::Tls(format!(
+            "{url} needs the `rustls-tls` or `native-tls` feature"
+        )))
+    }
+}
+
+/// TLS error with the cause, as reqwest only reports a "builder error" itself.
+#[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+fn tls_error(context: &str, error: reqwest::Error) -> Error {
+    match std::error::Error::source(&error) {
+        Some(source) => Error::Tls(format!("{context}: {source}")),
+        None => Error::Tls(String::from(context)),
+    }
+}
+
+/// Settings of the HTTP client created by [`OpenAiClient::new`].
+#[derive(Debug, Default, Clone)]
+pub struct HttpSettings {
+    /// Timeout of establishing connections, independent of the timeout of the whole request.
+    pub connect_timeout: Option<Duration>,
+    /// Proxy of all the requests. The proxies from the `HTTP_PROXY` / `HTTPS_PROXY` /
+    /// `ALL_PROXY` environment variables are used if unset.
+    pub proxy: Option<Proxy>,
+    /// Extra root certificates and the client certificate.
+    pub tls: Tls,
+}
+
+impl HttpSettings {
+    fn client(self) -> Result<Client, Error> {
+        let mut builder = self
+            .tls
+            .configure(ClientBuilder::new().timeout(REQUEST_TIMEOUT))?;
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(Proxy { url, credentials }) = self.proxy {
+            let mut proxy = reqwest::Proxy::all(url)?.no_proxy(reqwest::NoProxy::from_env());
+            if let Some((username, password)) = credentials {
+                proxy = proxy.basic_auth(&username, &password);
+            }
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// Endpoint used for the requests, shared with the clones of the client.
+#[derive(Debug, Default)]
+struct ActiveEndpoint {
+    /// Index of the base URL.
+    index: usize,
+    /// When to send the requests to the primary endpoint first again after failing over.
+    retry_primary_at: Option<Instant>,
+}
+
 /// OpenAI REST API client.
+///
+/// If fallback URLs are configured, requests failing with connection errors, timeouts,
+/// HTTP 429 or 5xx are retried against the next endpoint. The last healthy endpoint is then
+/// used for subsequent requests, including by the clones of the client.
+///
+/// There are no background health checks. Instead, the first request five minutes after
+/// failing over is sent to the primary endpoint as a probe, and the client switches back to it
+/// if it is healthy.
+#[derive(Clone)]
 pub struct OpenAiClient {
     client: Client,
-    endpoint: String,
+    base_urls: Vec<String>,
+    active_endpoint: Arc<Mutex<ActiveEndpoint>>,
+    /// Chat endpoint path relative to the base URLs.
+    endpoint: &'static str,
+    api_version: Option<String>,
+    auth_headers: HeaderMap,
+    /// Custom headers of every request, overridden by the auth headers.
+    headers: HeaderMap,
+    entra_id: Option<Arc<TokenProvider>>,
+    retry_policy: Option<RetryPolicy>,
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl OpenAiClient {
     /// Create new OpenAI API client.
-    pub fn new(auth: Auth, base_url: String, api_version: Option<String>) -> Result<Self, Error> {
-        let client = ClientBuilder::new()
-            .default_headers(auth.try_into()?)
-            .timeout(REQUEST_TIMEOUT)
-            .build()?;
-
-        let endpoint = build_url(base_url, api_version);
+    pub fn new(
+        auth: Auth,
+        base_url: String,
+        api_version: Option<String>,
+        http: HttpSettings,
+    ) -> Result<Self, Error> {
+        ensure_tls_support(&base_url)?;
+        let client = http.client()?;
 
-        Ok(Self { client, endpoint })
+        Ok(Self {
+            entra_id: entra_id_provider(&auth, &client),
+            client,
+            base_urls: vec![base_url],
+            active_endpoint: Arc::default(),
+            endpoint: CHAT_COMPLETIONS_ENDPOINT,
+            api_version,
+            auth_headers: auth.try_into()?,
+            headers: HeaderMap::new(),
+            retry_policy: None,
+            rate_limiter: None,
+        })
     }
 
     /// Create new OpenAI API client with custom [`reqwest::Client`].
@@ -90,22 +326,200 @@ impl OpenAiClient {
     pub fn new_with_client(client: Client, base_url: String, api_version: Option<String>) -> Self {
         Self {
             client,
-            endpoint: build_url(base_url, api_version),
+            base_urls: vec![base_url],
+            active_endpoint: Arc::default(),
+            endpoint: CHAT_COMPLETIONS_ENDPOINT,
+            api_version,
+            auth_headers: HeaderMap::new(),
+            headers: HeaderMap::new(),
+            entra_id: None,
+            retry_policy: None,
+            rate_limiter: None,
         }
     }
 
-    /// Request chat completion message.
-    pub async fn chat_completions(
-        &mut self,
-        body: ChatCompletionsBody,
-    ) -> Result<ChatCompletions, Error> {
-        let response = self
+    /// Add fallback base URLs of the same deployment, e.g., in other Azure regions, tried in
+    /// order if the current endpoint is unavailable.
+    pub fn with_fallback_urls(mut self, base_urls: Vec<String>) -> Self {
+        self.base_urls.extend(base_urls);
+
+        self
+    }
+
+    /// Use the chat endpoint at `endpoint` path relative to the base URLs instead of
+    /// `chat/completions`, e.g., `api/chat` of Ollama.
+    pub fn with_endpoint(mut self, endpoint: &'static str) -> Self {
+        self.endpoint = endpoint;
+
+        self
+    }
+
+    /// Send the custom `headers` with every request, e.g., `HTTP-Referer` and `X-Title` of
+    /// OpenRouter. The auth headers take precedence over the custom ones.
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Result<Self, Error> {
+        for (name, value) in headers {
+            let header_name =
+                HeaderName::from_str(&name).map_err(|_| Error::InvalidHeader(name.clone()))?;
+            let header_value =
+                HeaderValue::from_str(&value).map_err(|_| Error::InvalidHeader(name))?;
+            self.headers.append(header_name, header_value);
+        }
+
+        Ok(self)
+    }
+
+    /// Retry failed requests according to the policy.
+    pub fn with_retry_policy(mut self, retry_policy: Option<RetryPolicy>) -> Self {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Queue requests exceeding the rate limits. The limits are shared with the clones of
+    /// the client.
+    pub fn with_rate_limits(mut self, rate_limits: Option<RateLimits>) -> Self {
+        self.rate_limiter = rate_limits.map(|limits| Arc::new(RateLimiter::new(limits)));
+
+        self
+    }
+
+    /// Replace the credentials used for subsequent requests.
+    ///
+    /// The new auth headers take precedence over the default headers of a custom
+    /// [`reqwest::Client`].
+    pub fn set_auth(&mut self, auth: Auth) -> Result<(), Error> {
+        self.entra_id = entra_id_provider(&auth, &self.client);
+        self.auth_headers = auth.try_into()?;
+
+        Ok(())
+    }
+
+    /// Send chat request, returning once the response headers are received.
+    ///
+    /// Fails over to the next endpoint if the current one is unhealthy. If all endpoints are
+    /// unhealthy, the request is retried according to the [`RetryPolicy`], if any, and the
+    /// result of the last attempt is returned.
+    ///
+    /// With rate limits, the request waits until it fits into them, counting the
+    /// `prompt_tokens` estimate towards the tokens per minute.
+    pub async fn send<B: Serialize>(
+        &self,
+        body: &B,
+        prompt_tokens: usize,
+    ) -> Result<Response, Error> {
+        let auth_headers = self.auth_headers().await?;
+        let mut retry = 0;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire(prompt_tokens).await;
+            }
+
+            let result = self.send_to_endpoints(&auth_headers, body).await;
+
+            let Some(retry_policy) = &self.retry_policy else {
+                return Ok(result?);
+            };
+            if is_healthy(&result) || retry == retry_policy.max_retries {
+                return Ok(result?);
+            }
+
+            let delay = match result.as_ref().ok().and_then(retry_after) {
+                Some(delay) if delay > retry_policy.max_backoff => return Ok(result?),
+                Some(delay) => delay,
+                None => retry_policy.backoff(retry),
+            };
+            tokio::time::sleep(delay).await;
+
+            retry += 1;
+        }
+    }
+
+    /// Send GET request to `endpoint` relative to the active base URL, e.g., `models`.
+    ///
+    /// The request is neither retried nor failed over.
+    pub async fn get(&self, endpoint: &str) -> Result<Response, Error> {
+        let auth_headers = self.auth_headers().await?;
+        let index = self.active_endpoint.lock().expect("not poisoned").index;
+        let base_url = &self.base_urls[index];
+        let url = build_url(base_url, endpoint, &self.api_version);
+
+        Ok(self
             .client
-            .post(self.endpoint.clone())
-            .json(&body)
+            .get(url)
+            .headers(self.headers.clone())
+            .headers(auth_headers)
             .send()
-            .await?;
+            .await?)
+    }
+
+    /// Auth headers, with a valid Entra ID token if Entra ID is used.
+    async fn auth_headers(&self) -> Result<HeaderMap, Error> {
+        let mut auth_headers = self.auth_headers.clone();
+        if let Some(entra_id) = &self.entra_id {
+            let token = entra_id
+                .token()
+                .await
+                .map_err(|e| Error::EntraId(e.without_url()))?;
+            auth_headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))?,
+            );
+        }
+
+        Ok(auth_headers)
+    }
+
+    /// Send the request to the endpoints starting from the active one until one is healthy.
+    ///
+    /// After [`PRIMARY_RECOVERY_INTERVAL`] since failing over, the primary endpoint is tried
+    /// first.
+    async fn send_to_endpoints<B: Serialize>(
+        &self,
+        auth_headers: &HeaderMap,
+        body: &B,
+    ) -> Result<Response, reqwest::Error> {
+        let first = {
+            let active = self.active_endpoint.lock().expect("not poisoned");
+            if active
+                .retry_primary_at
+                .is_some_and(|at| Instant::now() >= at)
+            {
+                0
+            } else {
+                active.index
+            }
+        };
+        let mut primary_tried = false;
+        let mut attempt = 0;
 
+        loop {
+            let index = (first + attempt) % self.base_urls.len();
+            primary_tried |= index == 0;
+            let url = build_url(&self.base_urls[index], self.endpoint, &self.api_version);
+            let result = self.build_request(&url, auth_headers, body).send().await;
+
+            if is_healthy(&result) {
+                let mut active = self.active_endpoint.lock().expect("not poisoned");
+                active.index = index;
+                if index == 0 {
+                    active.retry_primary_at = None;
+                } else if primary_tried {
+                    active.retry_primary_at = Some(Instant::now() + PRIMARY_RECOVERY_INTERVAL);
+                }
+
+                return result;
+            }
+
+            attempt += 1;
+            if attempt == self.base_urls.len() {
+                return result;
+            }
+        }
+    }
+
+    /// Read chat response body, converting HTTP errors into [`ApiError`].
+    pub async fn read_response<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
         if response.status().is_success() {
             Ok(response.json().await?)
         } else {
@@ -115,24 +529,83 @@ impl OpenAiClient {
                 .await
                 .unwrap_or(String::from("<invalid UTF-8>"));
 
-            let description = serde_json::from_str::<ErrorBody>(&body)
-                .map(|e| e.error.message)
-                .unwrap_or(body);
+            let (description, metadata) = match serde_json::from_str::<ErrorBody>(&body) {
+                Ok(ErrorBody {
+                    error: ErrorField::OpenAi(error),
+                }) => (error.message, error.metadata),
+                Ok(ErrorBody {
+                    error: ErrorField::Message(message),
+                }) => (message, None),
+                Err(_) => (body, None),
+            };
 
             Err(ApiError {
                 status,
                 description,
+                metadata,
             }
             .into())
         }
     }
+
+    /// Build a chat request with the custom and auth headers.
+    fn build_request<B: Serialize>(
+        &self,
+        url: &str,
+        auth_headers: &HeaderMap,
+        body: &B,
+    ) -> RequestBuilder {
+        self.client
+            .post(url)
+            .headers(self.headers.clone())
+            .headers(auth_headers.clone())
+            .json(body)
+    }
 }
 
-fn build_url(base_url: String, api_version: Option<String>) -> String {
+/// Entra ID token provider if `auth` uses Entra ID.
+fn entra_id_provider(auth: &Auth, client: &Client) -> Option<Arc<TokenProvider>> {
+    match auth {
+        Auth::EntraId(credential) => Some(Arc::new(TokenProvider::new(
+            credential.clone(),
+            client.clone(),
+        ))),
+        _ => None,
+    }
+}
+
+/// Whether the endpoint responded and is not overloaded or failing.
+fn is_healthy(result: &Result<Response, reqwest::Error>) -> bool {
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(_) => false,
+    }
+}
+
+/// Delay requested by the `retry-after-ms` or `Retry-After` (in seconds) headers.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.0)
+    };
+
+    header("retry-after-ms")
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .or_else(|| header("retry-after").map(Duration::from_secs_f64))
+}
+
+fn build_url(base_url: &str, endpoint: &str, api_version: &Option<String>) -> String {
     if let Some(version) = api_version {
-        format!("{base_url}{CHAT_COMPLETIONS_ENDPOINT}?api-version={version}")
+        format!("{base_url}{endpoint}?api-version={version}")
     } else {
-        format!("{base_url}{CHAT_COMPLETIONS_ENDPOINT}")
+        format!("{base_url}{endpoint}")
     }
 }
 
@@ -143,6 +616,10 @@ pub enum Error {
     #[error("Non ASCII / non visible characters in API key")]
     InvalidCharacersInApiKey(#[from] InvalidHeaderValue),
 
+    /// Invalid custom header name or value.
+    #[error("Invalid name or value of header `{0}`")]
+    InvalidHeader(String),
+
     /// Reqwest error.
     #[error("Request error: {0}")]
     Request(reqwest::Error),
@@ -150,6 +627,14 @@ pub enum Error {
     /// API (HTTP) error.
     #[error("{0}")]
     Api(#[from] ApiError),
+
+    /// Invalid custom root or client certificate, or an HTTPS URL without a TLS backend.
+    #[error("TLS configuration error: {0}")]
+    Tls(String),
+
+    /// Failed to obtain Entra ID token.
+    #[error("Entra ID token error: {0}")]
+    EntraId(reqwest::Error),
 }
 
 impl From<reqwest::Error> for Error {
@@ -159,6 +644,33 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl Error {
+    /// Whether the API rejected the credentials (HTTP 401 or 403 other than a moderation
+    /// rejection).
+    pub fn is_auth_error(&self) -> bool {
+        matches!(
+            self,
+            Error::Api(ApiError { status, .. })
+                if *status == StatusCode::UNAUTHORIZED || *status == StatusCode::FORBIDDEN
+        ) && self.moderation().is_none()
+    }
+
+    /// OpenRouter moderation metadata if the input was flagged (HTTP 403 with the moderation
+    /// `reasons`).
+    pub fn moderation(&self) -> Option<&Value> {
+        match self {
+            Error::Api(ApiError {
+                status,
+                metadata: Some(metadata),
+                ..
+            }) if *status == StatusCode::FORBIDDEN && metadata.get("reasons").is_some() => {
+                Some(metadata)
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Error in case of HTTP status != 200 OK.
 #[derive(Debug, thiserror::Error)]
 pub struct ApiError {
@@ -166,6 +678,8 @@ pub struct ApiError {
     pub status: StatusCode,
     /// Error description.
     pub description: String,
+    /// Provider specific error details, e.g., OpenRouter moderation reasons and flagged input.
+    pub metadata: Option<Value>,
 }
 
 impl Display for ApiError {
@@ -177,13 +691,163 @@ impl Display for ApiError {
 /// Possible error body (might be incomplete type).
 #[derive(Debug, Deserialize)]
 pub struct ErrorBody {
-    /// Internal `error` JSON object.
-    error: OpenAiError,
+    /// Internal `error` JSON object, or the error message.
+    error: ErrorField,
 }
 
-/// Possible `error` field (fields other than `message` omitted).
+/// `error` field of the error body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ErrorField {
+    /// OpenAI `error` JSON object.
+    OpenAi(OpenAiError),
+    /// Error message, e.g., returned by Ollama.
+    Message(String),
+}
+
+/// Possible `error` field (fields other than `message` and `metadata` omitted).
 #[derive(Debug, Deserialize)]
 pub struct OpenAiError {
     /// Field `message` of `error` JSON object.
     message: String,
+    /// Field `metadata` of `error` JSON object, used by OpenRouter.
+    metadata: Option<Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_client::mock::mock_api;
+    use serde_json::json;
+
+    #[test]
+    fn retry_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+        };
+
+        let first = policy.backoff(0);
+        assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+
+        let third = policy.backoff(2);
+        assert!(third >= Duration::from_secs(2) && third <= Duration::from_secs(4));
+
+        let capped = policy.backoff(10);
+        assert!(capped >= Duration::from_millis(2500) && capped <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn custom_headers() {
+        let client = OpenAiClient::new(
+            Auth::Token(String::from("token")),
+            String::from("http://localhost:8080/v1/"),
+            None,
+            HttpSettings::default(),
+        )
+        .unwrap()
+        .with_headers(vec![
+            (String::from("X-Title"), String::from("jutella")),
+            (String::from("Authorization"), String::from("Bearer other")),
+        ])
+        .unwrap();
+
+        let request = client
+            .build_request("http://localhost:8080/v1/", &client.auth_headers, &())
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-title"], "jutella");
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer token");
+
+        assert!(matches!(
+            client.with_headers(vec![(String::from("X Title"), String::new())]),
+            Err(Error::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn https_needs_tls_backend() {
+        assert!(ensure_tls_support("http://localhost:11434/").is_ok());
+        assert_eq!(
+            ensure_tls_support("https://api.openai.com/v1/").is_ok(),
+            cfg!(any(feature = "rustls-tls", feature = "native-tls"))
+        );
+    }
+
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    #[test]
+    fn custom_certificates() {
+        const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBhjCCASugAwIBAgIUCDEPxVRiU5FpIMW0KcWLgXb7r00wCgYIKoZIzj0EAwIw
+FzEVMBMGA1UEAwwManV0ZWxsYS10ZXN0MCAXDTI2MTAxNjEwNTk0NFoYDzIxMjYw
+OTIyMTA1OTQ0WjAXMRUwEwYDVQQDDAxqdXRlbGxhLXRlc3QwWTATBgcqhkjOPQIB
+BggqhkjOPQMBBwNCAAQENTYHmNw5OLswkMa1nAVwN8wsy2XE2Prz9n1Ta+tN+tkC
+qmHx4OXe6NnHkttU+EZH7JQ9Uhsu5UiWpuxPQ5O0o1MwUTAdBgNVHQ4EFgQUqR4E
+6wpJAsXqtUWD66l5f8gy/LkwHwYDVR0jBBgwFoAUqR4E6wpJAsXqtUWD66l5f8gy
+/LkwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBGAiEA70BdbKvY9GIM
+MjEzJm7pUNhQihxNM1DN52ILqNe4L/oCIQCXO1/MYEB93lt5Sniq38aRjOwYx425
+2fFNFxFqfttNyQ==
+-----END CERTIFICATE-----
+";
+        let settings = |tls| HttpSettings {
+            tls,
+            ..Default::default()
+        };
+
+        assert!(settings(Tls {
+            root_certificates: Some(CERTIFICATE.as_bytes().to_vec()),
+            client_certificate: None,
+        })
+        .client()
+        .is_ok());
+
+        assert!(matches!(
+            settings(Tls {
+                root_certificates: Some(b"not a certificate".to_vec()),
+                client_certificate: None,
+            })
+            .client(),
+            Err(Error::Tls(_))
+        ));
+        assert!(matches!(
+            settings(Tls {
+                root_certificates: None,
+                client_certificate: Some((CERTIFICATE.as_bytes().to_vec(), Vec::new())),
+            })
+            .client(),
+            Err(Error::Tls(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn failover_and_recovery() {
+        let (primary, primary_requests) =
+            mock_api(vec![(503, json!({})), (200, json!({}))], Duration::ZERO);
+        let (fallback, fallback_requests) =
+            mock_api(vec![(200, json!({})), (200, json!({}))], Duration::ZERO);
+        let client = OpenAiClient::new(Auth::None, primary, None, HttpSettings::default())
+            .unwrap()
+            .with_fallback_urls(vec![fallback]);
+        let requests =
+            |requests: &Arc<std::sync::Mutex<Vec<Value>>>| requests.lock().unwrap().len();
+
+        // Failed over and sticking to the fallback.
+        client.send(&json!({}), 0).await.unwrap();
+        client.send(&json!({}), 0).await.unwrap();
+        assert_eq!(requests(&primary_requests), 1);
+        assert_eq!(requests(&fallback_requests), 2);
+
+        // Back to the recovered primary after the recovery interval.
+        client
+            .active_endpoint
+            .lock()
+            .unwrap()
+            .retry_primary_at
+            .replace(Instant::now());
+        client.send(&json!({}), 0).await.unwrap();
+        assert_eq!(requests(&primary_requests), 2);
+        assert_eq!(requests(&fallback_requests), 2);
+        assert_eq!(client.active_endpoint.lock().unwrap().index, 0);
+    }
 }
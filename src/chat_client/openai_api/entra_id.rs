@@ -0,0 +1,203 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Microsoft Entra ID (Azure AD) OAuth2 bearer tokens for Azure OpenAI.
+
+use reqwest::Client;
+use serde::Deserialize;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+const AUTHORITY: &str = "https://login.microsoftonline.com";
+const SCOPE: &str = "https://cognitiveservices.azure.com/.default";
+const RESOURCE: &str = "https://cognitiveservices.azure.com";
+const IMDS_TOKEN_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const IMDS_API_VERSION: &str = "2018-02-01";
+
+/// Tokens are refreshed this long before they expire.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+/// Credential to obtain Entra ID tokens with.
+#[derive(Clone, PartialEq, Eq)]
+pub enum EntraIdCredential {
+    /// Client credentials flow of an app registration (service principal).
+    ClientSecret {
+        /// Directory (tenant) ID.
+        tenant_id: String,
+        /// Application (client) ID.
+        client_id: String,
+        /// Client secret.
+        client_secret: String,
+    },
+    /// Managed identity of the Azure host.
+    ManagedIdentity {
+        /// Client ID of a user-assigned identity. The system-assigned identity is used if
+        /// `None`.
+        client_id: Option<String>,
+    },
+}
+
+impl fmt::Debug for EntraIdCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntraIdCredential::ClientSecret {
+                tenant_id,
+                client_id,
+                ..
+            } => f
+                .debug_struct("ClientSecret")
+                .field("tenant_id", tenant_id)
+                .field("client_id", client_id)
+                .finish_non_exhaustive(),
+            EntraIdCredential::ManagedIdentity { client_id } => f
+                .debug_struct("ManagedIdentity")
+                .field("client_id", client_id)
+                .finish(),
+        }
+    }
+}
+
+/// Token endpoint response (fields not used omitted).
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Seconds, a string in managed identity responses.
+    expires_in: ExpiresIn,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExpiresIn {
+    Number(u64),
+    String(String),
+}
+
+impl ExpiresIn {
+    fn seconds(&self) -> Option<u64> {
+        match self {
+            ExpiresIn::Number(seconds) => Some(*seconds),
+            ExpiresIn::String(seconds) => seconds.parse().ok(),
+        }
+    }
+}
+
+/// Cached bearer token.
+struct Token {
+    value: String,
+    refresh_at: Instant,
+}
+
+/// Provider of Entra ID bearer tokens, refreshing them before expiry.
+pub struct TokenProvider {
+    credential: EntraIdCredential,
+    client: Client,
+    token: Mutex<Option<Token>>,
+}
+
+impl TokenProvider {
+    pub fn new(credential: EntraIdCredential, client: Client) -> Self {
+        Self {
+            credential,
+            client,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Valid bearer token, requesting a new one if the cached one is about to expire.
+    pub async fn token(&self) -> Result<String, reqwest::Error> {
+        // Holding the lock while requesting makes concurrent requests wait for one token.
+        let mut token = self.token.lock().await;
+
+        if let Some(token) = token.as_ref().filter(|t| t.refresh_at > Instant::now()) {
+            return Ok(token.value.clone());
+        }
+
+        let requested_at = Instant::now();
+        let response: TokenResponse = self
+            .request()
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let lifetime = Duration::from_secs(response.expires_in.seconds().unwrap_or_default());
+        *token = Some(Token {
+            value: response.access_token.clone(),
+            refresh_at: requested_at + lifetime.saturating_sub(EXPIRY_MARGIN),
+        });
+
+        Ok(response.access_token)
+    }
+
+    fn request(&self) -> reqwest::RequestBuilder {
+        match &self.credential {
+            EntraIdCredential::ClientSecret {
+                tenant_id,
+                client_id,
+                client_secret,
+            } => self
+                .client
+                .post(format!("{AUTHORITY}/{tenant_id}/oauth2/v2.0/token"))
+                .form(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_id", client_id),
+                    ("client_secret", client_secret),
+                    ("scope", SCOPE),
+                ]),
+            EntraIdCredential::ManagedIdentity { client_id } => {
+                let mut query = vec![("api-version", IMDS_API_VERSION), ("resource", RESOURCE)];
+                if let Some(client_id) = client_id {
+                    query.push(("client_id", client_id));
+                }
+
+                self.client
+                    .get(IMDS_TOKEN_ENDPOINT)
+                    .header("Metadata", "true")
+                    .query(&query)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_response() {
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"token_type":"Bearer","expires_in":3599,"access_token":"token"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.expires_in.seconds(), Some(3599));
+
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"token","expires_in":"86399","token_type":"Bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.expires_in.seconds(), Some(86399));
+    }
+}
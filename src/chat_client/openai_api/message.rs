@@ -22,7 +22,7 @@
 
 //! OpenAI API Message types.
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::value::Value;
 
 /// Conversation message.
@@ -74,6 +74,14 @@ impl UserMessage {
             name: None,
         }
     }
+
+    /// User message of the participant `name`.
+    pub fn with_name(content: String, name: String) -> Self {
+        Self {
+            content,
+            name: Some(name),
+        }
+    }
 }
 
 /// Assistant message.
@@ -153,8 +161,12 @@ pub enum Role {
 pub struct GenericMessage {
     /// The role of the message author.
     role: Role,
-    /// The contents of the message.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// The contents of the message. Array-form content is normalized to its text parts.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_content",
+        skip_serializing_if = "Option::is_none"
+    )]
     content: Option<String>,
     /// An optional name for the participant. Provides the model information
     /// to differentiate between participants of the same role.
@@ -169,6 +181,94 @@ pub struct GenericMessage {
     /// Tool call that this message is responding to.
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
+    /// Images generated by the assistant, e.g., by OpenRouter image models.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    images: Option<Vec<ImagePart>>,
+}
+
+/// Message content, either a string or an array of content parts.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// Content part, e.g., `{ "type": "text", "text": "..." }` (fields not used omitted).
+#[derive(Deserialize)]
+struct ContentPart {
+    #[serde(rename = "type", default)]
+    kind: String,
+    text: Option<String>,
+}
+
+/// Deserialize the content as a string, also accepting the array of content parts returned
+/// by some providers, e.g., Mistral reasoning models. The text parts are concatenated; other
+/// parts, like reasoning or audio, are skipped. Content without text parts is `None`.
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Content>::deserialize(deserializer)? {
+        None => None,
+        Some(Content::Text(text)) => Some(text),
+        Some(Content::Parts(parts)) => parts
+            .into_iter()
+            .filter(|part| part.kind == "text")
+            .filter_map(|part| part.text)
+            .reduce(|text, part| text + &part),
+    })
+}
+
+/// Image content part `{ "type": "image_url", "image_url": { "url": "..." } }`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct ImagePart {
+    image_url: ImageUrl,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct ImageUrl {
+    url: String,
+}
+
+/// Image generated by the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// Image URL, usually a base64 data URL, e.g., "data:image/png;base64,...".
+    pub url: String,
+}
+
+impl Image {
+    /// Media type of a data URL, e.g., "image/png".
+    pub fn media_type(&self) -> Option<&str> {
+        let (media_type, _) = self.url.strip_prefix("data:")?.split_once([';', ','])?;
+
+        Some(media_type).filter(|media_type| !media_type.is_empty())
+    }
+}
+
+impl GenericMessage {
+    /// The role of the message author.
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// The contents of the message.
+    pub fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
+    /// Take the images generated by the assistant out of the message.
+    pub fn take_images(&mut self) -> Vec<Image> {
+        self.images
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|part| Image {
+                url: part.image_url.url,
+            })
+            .collect()
+    }
 }
 
 impl From<Message> for GenericMessage {
@@ -191,6 +291,7 @@ impl From<SystemMessage> for GenericMessage {
             refusal: None,
             tool_calls: None,
             tool_call_id: None,
+            images: None,
         }
     }
 }
@@ -204,6 +305,7 @@ impl From<UserMessage> for GenericMessage {
             refusal: None,
             tool_calls: None,
             tool_call_id: None,
+            images: None,
         }
     }
 }
@@ -224,6 +326,7 @@ impl From<AssistantMessage> for GenericMessage {
             refusal,
             tool_calls,
             tool_call_id: None,
+            images: None,
         }
     }
 }
@@ -242,6 +345,7 @@ impl From<ToolMessage> for GenericMessage {
             refusal: None,
             tool_calls: None,
             tool_call_id: Some(tool_call_id),
+            images: None,
         }
     }
 }
@@ -331,3 +435,52 @@ impl TryFrom<GenericMessage> for ToolMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn content_forms() {
+        let message: GenericMessage = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": "Hello!",
+        }))
+        .unwrap();
+        assert_eq!(message.content(), Some("Hello!"));
+
+        let message: GenericMessage = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [],
+        }))
+        .unwrap();
+        assert_eq!(message.content(), None);
+
+        // Mistral Magistral reasoning response.
+        let message: GenericMessage = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": [
+                {
+                    "type": "thinking",
+                    "thinking": [{ "type": "text", "text": "The user greets me." }],
+                },
+                { "type": "text", "text": "Hello! " },
+                { "type": "text", "text": "How can I help?" },
+            ],
+            "tool_calls": null,
+            "prefix": false,
+        }))
+        .unwrap();
+        assert_eq!(message.content(), Some("Hello! How can I help?"));
+
+        // Content without text parts.
+        let message: GenericMessage = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": [{ "type": "output_audio", "audio": { "id": "audio_1" } }],
+        }))
+        .unwrap();
+        assert_eq!(message.content(), None);
+    }
+}
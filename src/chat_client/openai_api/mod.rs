@@ -24,4 +24,7 @@
 
 pub mod chat_completions;
 pub mod client;
+pub mod entra_id;
 pub mod message;
+pub mod models;
+pub mod rate_limit;
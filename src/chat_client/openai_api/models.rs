@@ -0,0 +1,117 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! OpenAI API model catalog (`/models`) and model name resolution.
+
+use serde::Deserialize;
+
+/// Models endpoint relative to the base URL.
+pub const MODELS_ENDPOINT: &str = "models";
+
+/// Max edit distance of a mistyped model name to suggest a model for.
+const MAX_TYPO_DISTANCE: usize = 3;
+
+/// Model list response (fields not used omitted).
+#[derive(Debug, Deserialize)]
+pub struct ModelList {
+    /// Models available.
+    pub data: Vec<Model>,
+}
+
+/// Model object (fields not used omitted).
+#[derive(Debug, Deserialize)]
+pub struct Model {
+    /// Model identifier used in the requests.
+    pub id: String,
+}
+
+impl ModelList {
+    /// Name of the `model` in the catalog, matched ignoring case and surrounding whitespace.
+    /// Models without a tag also match the `:latest` tag, e.g., of Ollama models.
+    ///
+    /// Returns the closest model name as the error if the model is not in the catalog and
+    /// looks like a typo.
+    pub fn resolve(&self, model: &str) -> Result<&str, Option<&str>> {
+        let model = model.trim().to_lowercase();
+        let latest = format!("{model}:latest");
+
+        if let Some(found) = self
+            .data
+            .iter()
+            .find(|m| m.id.to_lowercase() == model)
+            .or_else(|| self.data.iter().find(|m| m.id.to_lowercase() == latest))
+        {
+            return Ok(&found.id);
+        }
+
+        Err(self
+            .data
+            .iter()
+            .map(|m| (m.id.as_str(), edit_distance(&model, &m.id.to_lowercase())))
+            .filter(|(_, distance)| *distance <= MAX_TYPO_DISTANCE)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(id, _)| id))
+    }
+}
+
+/// Levenshtein distance.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_model_names() {
+        let models: ModelList = serde_json::from_str(
+            r#"{
+                "object": "list",
+                "data": [
+                    { "id": "gpt-4o", "object": "model", "owned_by": "system" },
+                    { "id": "gpt-4o-mini", "object": "model", "owned_by": "system" },
+                    { "id": "llama3.2:latest", "object": "model", "owned_by": "library" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(models.resolve("gpt-4o-mini"), Ok("gpt-4o-mini"));
+        assert_eq!(models.resolve(" GPT-4o "), Ok("gpt-4o"));
+        assert_eq!(models.resolve("llama3.2"), Ok("llama3.2:latest"));
+        assert_eq!(models.resolve("gpt-4o-mnii"), Err(Some("gpt-4o-mini")));
+        assert_eq!(models.resolve("claude"), Err(None));
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Client-side rate limiting of requests.
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Requests-per-minute and tokens-per-minute limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Max requests per minute.
+    pub requests_per_minute: Option<usize>,
+    /// Max estimated prompt tokens per minute.
+    pub tokens_per_minute: Option<usize>,
+}
+
+/// Sliding window rate limiter queueing the requests over the limits.
+pub struct RateLimiter {
+    limits: RateLimits,
+    /// Start times and estimated tokens of the requests in the last minute.
+    ///
+    /// Queued requests reserve their future start times, so they are admitted in order
+    /// without holding the lock while waiting.
+    window: Mutex<VecDeque<(Instant, usize)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            window: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Wait until a request with `tokens` estimated prompt tokens fits into the limits.
+    ///
+    /// A request exceeding the tokens per minute on its own is admitted into an empty window.
+    /// The slot stays reserved if the waiting future is dropped.
+    pub async fn acquire(&self, tokens: usize) {
+        let start = self.reserve(tokens, Instant::now());
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// Reserve the earliest start time after the already reserved ones fitting into the limits.
+    fn reserve(&self, tokens: usize, now: Instant) -> Instant {
+        let mut window = self.window.lock().expect("rate limiter lock poisoned");
+
+        while window
+            .front()
+            .is_some_and(|(start, _)| now.duration_since(*start) >= WINDOW)
+        {
+            window.pop_front();
+        }
+
+        let mut start = window.back().map_or(now, |(last, _)| now.max(*last));
+        loop {
+            let active = window
+                .iter()
+                .skip_while(|(reserved, _)| start.duration_since(*reserved) >= WINDOW);
+
+            let Some((oldest, _)) = active.clone().next().copied() else {
+                break;
+            };
+
+            let requests_fit = self
+                .limits
+                .requests_per_minute
+                .is_none_or(|limit| active.clone().count() < limit);
+            let tokens_fit = self.limits.tokens_per_minute.is_none_or(|limit| {
+                active.map(|(_, tokens)| tokens).sum::<usize>() + tokens <= limit
+            });
+            if requests_fit && tokens_fit {
+                break;
+            }
+
+            start = oldest + WINDOW;
+        }
+
+        window.push_back((start, tokens));
+        start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_queue_over_limits() {
+        let limiter = RateLimiter::new(RateLimits {
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(100),
+        });
+        let start = Instant::now();
+
+        limiter.acquire(10).await;
+        limiter.acquire(10).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Third request waits for the first one to leave the window.
+        limiter.acquire(10).await;
+        assert!(start.elapsed() >= WINDOW);
+
+        // Oversized request is admitted once the window is empty.
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() >= 2 * WINDOW);
+    }
+
+    #[test]
+    fn queued_requests_reserve_slots_in_order() {
+        let limiter = RateLimiter::new(RateLimits {
+            requests_per_minute: Some(1),
+            tokens_per_minute: None,
+        });
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(10, now), now);
+        assert_eq!(limiter.reserve(10, now), now + WINDOW);
+        assert_eq!(limiter.reserve(10, now), now + 2 * WINDOW);
+    }
+}
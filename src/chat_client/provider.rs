@@ -0,0 +1,260 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Presets of OpenAI-compatible API providers.
+
+use crate::chat_client::{
+    ollama_api,
+    openai_api::{
+        chat_completions::{ChatCompletionsBody, ResponseFormat},
+        client::CHAT_COMPLETIONS_ENDPOINT,
+        models::MODELS_ENDPOINT,
+    },
+};
+use reqwest::Url;
+use serde::Deserialize;
+
+/// Default port of the Ollama server.
+const OLLAMA_PORT: u16 = 11434;
+
+/// Provider of an OpenAI-compatible chat completions API.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    /// OpenAI or any other fully compatible API, e.g., Azure OpenAI.
+    #[default]
+    OpenAi,
+    /// Groq.
+    Groq,
+    /// Mistral AI.
+    Mistral,
+    /// DeepSeek.
+    DeepSeek,
+    /// Local Ollama server, accessed via its native `/api/chat` endpoint.
+    Ollama,
+}
+
+impl Provider {
+    /// Provider inferred from the shape of the base API URL, `None` if it's not recognized.
+    ///
+    /// Azure OpenAI and OpenRouter are detected as [`Provider::OpenAi`], any server on
+    /// the Ollama port 11434 as [`Provider::Ollama`].
+    pub fn from_api_url(api_url: &str) -> Option<Self> {
+        let url = Url::parse(api_url).ok()?;
+        let host = url.host_str()?;
+
+        match host {
+            "api.openai.com" | "openrouter.ai" => Some(Provider::OpenAi),
+            "api.groq.com" => Some(Provider::Groq),
+            "api.mistral.ai" => Some(Provider::Mistral),
+            "api.deepseek.com" => Some(Provider::DeepSeek),
+            _ if is_azure(&url) => Some(Provider::OpenAi),
+            _ if url.port() == Some(OLLAMA_PORT) => Some(Provider::Ollama),
+            _ => None,
+        }
+    }
+
+    /// Whether an API key for `api_url` is sent in the Azure `api-key` header rather than
+    /// as a bearer token.
+    ///
+    /// Azure OpenAI URLs and the unrecognized URLs of [`Provider::OpenAi`], e.g., of a proxy
+    /// in front of Azure, use the `api-key` header, the other providers' URLs a bearer token.
+    pub fn uses_api_key_header(&self, api_url: &str) -> bool {
+        let Ok(url) = Url::parse(api_url) else {
+            return false;
+        };
+
+        is_azure(&url) || (*self == Provider::OpenAi && Provider::from_api_url(api_url).is_none())
+    }
+
+    /// Base API URL.
+    pub fn api_url(&self) -> &'static str {
+        match self {
+            Provider::OpenAi => "https://api.openai.com/v1/",
+            Provider::Groq => "https://api.groq.com/openai/v1/",
+            Provider::Mistral => "https://api.mistral.ai/v1/",
+            Provider::DeepSeek => "https://api.deepseek.com/v1/",
+            Provider::Ollama => "http://localhost:11434/",
+        }
+    }
+
+    /// Inexpensive general purpose model.
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            Provider::OpenAi => "gpt-4o-mini",
+            Provider::Groq => "llama-3.3-70b-versatile",
+            Provider::Mistral => "mistral-small-latest",
+            Provider::DeepSeek => "deepseek-chat",
+            Provider::Ollama => "llama3.2",
+        }
+    }
+
+    /// Chat endpoint relative to the API URL.
+    pub(crate) fn chat_endpoint(&self) -> &'static str {
+        match self {
+            Provider::Ollama => ollama_api::CHAT_ENDPOINT,
+            _ => CHAT_COMPLETIONS_ENDPOINT,
+        }
+    }
+
+    /// Model catalog endpoint relative to the API URL.
+    pub(crate) fn models_endpoint(&self) -> &'static str {
+        match self {
+            Provider::Ollama => ollama_api::TAGS_ENDPOINT,
+            _ => MODELS_ENDPOINT,
+        }
+    }
+
+    /// First parameter set in the request body that the provider doesn't support, if any.
+    pub(crate) fn unsupported_parameter(&self, body: &ChatCompletionsBody) -> Option<&'static str> {
+        use Provider::*;
+
+        let multiple_choices = body.completion_choices.is_some_and(|n| n > 1);
+        let json_schema = matches!(
+            body.response_format,
+            Some(ResponseFormat::JsonSchema { .. })
+        );
+
+        [
+            (
+                "logit_bias",
+                !body.logit_bias.is_empty(),
+                matches!(self, Groq | Mistral | DeepSeek | Ollama),
+            ),
+            (
+                "logprobs",
+                body.logprobs.is_some() || body.top_logprobs.is_some(),
+                matches!(self, Groq | Ollama),
+            ),
+            (
+                "modalities",
+                !body.modalities.is_empty(),
+                matches!(self, Groq | Mistral | DeepSeek | Ollama),
+            ),
+            (
+                "n",
+                multiple_choices,
+                matches!(self, Groq | DeepSeek | Ollama),
+            ),
+            (
+                "seed",
+                body.seed.is_some(),
+                matches!(self, Mistral | DeepSeek),
+            ),
+            (
+                "service_tier",
+                body.service_tier.is_some(),
+                matches!(self, Mistral | DeepSeek | Ollama),
+            ),
+            (
+                "reasoning_effort",
+                body.reasoning_effort.is_some(),
+                matches!(self, Mistral | DeepSeek | Ollama),
+            ),
+            ("response_format", json_schema, matches!(self, DeepSeek)),
+        ]
+        .into_iter()
+        .find_map(|(parameter, used, unsupported)| (used && unsupported).then_some(parameter))
+    }
+}
+
+/// Whether the URL is an Azure OpenAI endpoint.
+fn is_azure(url: &Url) -> bool {
+    url.host_str().is_some_and(|host| {
+        host.ends_with(".openai.azure.com")
+            || (host.ends_with(".azure.com") && url.path().starts_with("/openai"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_client::openai_api::chat_completions::ReasoningEffort;
+    use serde_json::json;
+
+    #[test]
+    fn provider_from_api_url() {
+        assert_eq!(
+            Provider::from_api_url("https://openrouter.ai/api/v1/"),
+            Some(Provider::OpenAi)
+        );
+        assert_eq!(
+            Provider::from_api_url("https://example.cognitiveservices.azure.com/openai/"),
+            Some(Provider::OpenAi)
+        );
+        assert_eq!(
+            Provider::from_api_url("https://api.groq.com/openai/v1/"),
+            Some(Provider::Groq)
+        );
+        assert_eq!(
+            Provider::from_api_url("http://127.0.0.1:11434/"),
+            Some(Provider::Ollama)
+        );
+        assert_eq!(Provider::from_api_url("http://localhost:8080/v1/"), None);
+        assert_eq!(Provider::from_api_url("not a url"), None);
+    }
+
+    #[test]
+    fn api_key_header() {
+        let openai = Provider::OpenAi;
+        assert!(openai.uses_api_key_header("https://example.openai.azure.com/"));
+        assert!(openai.uses_api_key_header("https://proxy.example.com/v1/"));
+        assert!(!openai.uses_api_key_header("https://api.openai.com/v1/"));
+        assert!(!openai.uses_api_key_header("https://openrouter.ai/api/v1/"));
+        assert!(!Provider::Groq.uses_api_key_header("https://proxy.example.com/v1/"));
+    }
+
+    #[test]
+    fn unsupported_parameters() {
+        let body = ChatCompletionsBody {
+            completion_choices: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(Provider::OpenAi.unsupported_parameter(&body), None);
+        assert_eq!(Provider::Mistral.unsupported_parameter(&body), None);
+        assert_eq!(Provider::Groq.unsupported_parameter(&body), Some("n"));
+
+        let body = ChatCompletionsBody {
+            completion_choices: Some(1),
+            response_format: Some(ResponseFormat::json_schema("scores", json!({})).build()),
+            ..Default::default()
+        };
+
+        assert_eq!(Provider::Groq.unsupported_parameter(&body), None);
+        assert_eq!(
+            Provider::DeepSeek.unsupported_parameter(&body),
+            Some("response_format")
+        );
+
+        let body = ChatCompletionsBody {
+            reasoning_effort: Some(ReasoningEffort::Low),
+            ..Default::default()
+        };
+
+        assert_eq!(Provider::OpenAi.unsupported_parameter(&body), None);
+        assert_eq!(
+            Provider::Ollama.unsupported_parameter(&body),
+            Some("reasoning_effort")
+        );
+    }
+}
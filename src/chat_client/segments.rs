@@ -0,0 +1,270 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Lexing of markdown responses into typed segments.
+
+/// Block-level segment of a markdown response, borrowing from the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment<'a> {
+    /// Markdown text, including inline formatting.
+    Text(&'a str),
+    /// Fenced code block.
+    Code {
+        /// Language of the info string, e.g., "rust".
+        language: Option<&'a str>,
+        /// Code without the fences.
+        code: &'a str,
+    },
+    /// Display math block delimited by `$$` or `\[` and `\]`, without the delimiters.
+    Math(&'a str),
+    /// Table with a header row.
+    Table {
+        /// Header cells.
+        header: Vec<&'a str>,
+        /// Body rows of cells.
+        rows: Vec<Vec<&'a str>>,
+    },
+}
+
+impl<'a> Segment<'a> {
+    /// Split the markdown `text` into segments.
+    ///
+    /// Unterminated code and math blocks extend to the end of the text, e.g., in a truncated
+    /// response. Blank text between the blocks is skipped.
+    pub fn parse(text: &'a str) -> Vec<Segment<'a>> {
+        let lines = lines(text);
+        let mut segments = Vec::new();
+        let mut text_start = None;
+        let mut index = 0;
+
+        while index < lines.len() {
+            let (offset, _) = lines[index];
+
+            let block = code_block(text, &lines[index..])
+                .or_else(|| math_block(text, &lines[index..]))
+                .or_else(|| table(&lines[index..]));
+
+            match block {
+                Some((segment, consumed)) => {
+                    if let Some(start) = text_start.take() {
+                        push_text(&mut segments, &text[start..offset]);
+                    }
+                    segments.push(segment);
+                    index += consumed;
+                }
+                None => {
+                    text_start.get_or_insert(offset);
+                    index += 1;
+                }
+            }
+        }
+
+        if let Some(start) = text_start {
+            push_text(&mut segments, &text[start..]);
+        }
+
+        segments
+    }
+}
+
+/// Lines with their byte offsets, without the line endings.
+fn lines(text: &str) -> Vec<(usize, &str)> {
+    let mut offset = 0;
+
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line.trim_end_matches(['\n', '\r']))
+        })
+        .collect()
+}
+
+fn push_text<'a>(segments: &mut Vec<Segment<'a>>, text: &'a str) {
+    let text = text.trim_matches(['\n', '\r']);
+    if !text.trim().is_empty() {
+        segments.push(Segment::Text(text));
+    }
+}
+
+/// Text between the lines `first` and `last` (exclusive), or to the end of the text.
+fn between<'a>(text: &'a str, lines: &[(usize, &str)], first: usize, last: usize) -> &'a str {
+    let start = lines.get(first).map_or(text.len(), |(offset, _)| *offset);
+    let end = lines.get(last).map_or(text.len(), |(offset, _)| *offset);
+
+    text[start..end.max(start)].trim_end_matches(['\n', '\r'])
+}
+
+/// Fenced code block starting at the first line and the number of lines it spans.
+fn code_block<'a>(text: &'a str, lines: &[(usize, &'a str)]) -> Option<(Segment<'a>, usize)> {
+    let line = lines[0].1.trim_start();
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let fence_len = line.chars().take_while(|c| *c == fence_char).count();
+    if fence_len < 3 {
+        return None;
+    }
+
+    let info = line[fence_len..].trim();
+    let language = info.split_whitespace().next();
+
+    let closing = lines[1..].iter().position(|(_, line)| {
+        let line = line.trim();
+        line.len() >= fence_len && line.chars().all(|c| c == fence_char)
+    });
+    let last = closing.map_or(lines.len(), |position| position + 1);
+
+    Some((
+        Segment::Code {
+            language,
+            code: between(text, lines, 1, last),
+        },
+        closing.map_or(lines.len(), |position| position + 2),
+    ))
+}
+
+/// Display math block starting at the first line and the number of lines it spans.
+fn math_block<'a>(text: &'a str, lines: &[(usize, &'a str)]) -> Option<(Segment<'a>, usize)> {
+    let line = lines[0].1.trim();
+    let (open, close) = [("$$", "$$"), ("\\[", "\\]")]
+        .into_iter()
+        .find(|(open, _)| line.starts_with(open))?;
+
+    // Single-line block, e.g., `$$ x^2 $$`.
+    let rest = &line[open.len()..];
+    if let Some(math) = rest.strip_suffix(close) {
+        return Some((Segment::Math(math.trim()), 1));
+    }
+    if !rest.trim().is_empty() {
+        return None;
+    }
+
+    let closing = lines[1..].iter().position(|(_, line)| line.trim() == close);
+    let last = closing.map_or(lines.len(), |position| position + 1);
+
+    Some((
+        Segment::Math(between(text, lines, 1, last).trim()),
+        closing.map_or(lines.len(), |position| position + 2),
+    ))
+}
+
+/// Table starting at the first line and the number of lines it spans.
+fn table<'a>(lines: &[(usize, &'a str)]) -> Option<(Segment<'a>, usize)> {
+    let is_row = |line: &str| line.trim_start().starts_with('|');
+    let is_delimiter = |line: &str| {
+        let cells = cells(line);
+        !cells.is_empty()
+            && cells.iter().all(|cell| {
+                let cell = cell.trim_start_matches(':').trim_end_matches(':');
+                !cell.is_empty() && cell.chars().all(|c| c == '-')
+            })
+    };
+
+    let (_, header) = lines.first()?;
+    let (_, delimiter) = lines.get(1)?;
+    if !is_row(header) || !is_delimiter(delimiter) {
+        return None;
+    }
+
+    let rows = lines[2..]
+        .iter()
+        .take_while(|(_, line)| is_row(line))
+        .map(|(_, line)| cells(line))
+        .collect::<Vec<_>>();
+    let consumed = rows.len() + 2;
+
+    Some((
+        Segment::Table {
+            header: cells(header),
+            rows,
+        },
+        consumed,
+    ))
+}
+
+/// Cells of a table row.
+fn cells(row: &str) -> Vec<&str> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+
+    row.split('|').map(str::trim).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let response = "Here is the code:\n\n\
+            ```rust\n\
+            fn main() {}\n\
+            ```\n\
+            The area is\n\
+            $$\n\
+            \\pi r^2\n\
+            $$\n\
+            | Shape | Area |\n\
+            |:------|-----:|\n\
+            | Circle | $\\pi r^2$ |\n\
+            | Square | $a^2$ |\n\
+            \n\
+            Done.";
+
+        assert_eq!(
+            Segment::parse(response),
+            vec![
+                Segment::Text("Here is the code:"),
+                Segment::Code {
+                    language: Some("rust"),
+                    code: "fn main() {}",
+                },
+                Segment::Text("The area is"),
+                Segment::Math("\\pi r^2"),
+                Segment::Table {
+                    header: vec!["Shape", "Area"],
+                    rows: vec![vec!["Circle", "$\\pi r^2$"], vec!["Square", "$a^2$"]],
+                },
+                Segment::Text("Done."),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_blocks() {
+        assert_eq!(
+            Segment::parse("```\nlet x = 1;\nlet y"),
+            vec![Segment::Code {
+                language: None,
+                code: "let x = 1;\nlet y",
+            }]
+        );
+        assert_eq!(
+            Segment::parse("\\[ e^{i\\pi} + 1 = 0 \\]\n| not a table |\ntext"),
+            vec![
+                Segment::Math("e^{i\\pi} + 1 = 0"),
+                Segment::Text("| not a table |\ntext"),
+            ]
+        );
+        assert_eq!(Segment::parse(""), Vec::new());
+    }
+}
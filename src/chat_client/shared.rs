@@ -0,0 +1,96 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Process-wide registry of the resources shared by the clients.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tiktoken_rs::{tokenizer::Tokenizer, CoreBPE};
+
+/// Tokenizer initialization error.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct TokenizerInitError(String);
+
+/// Resources loaded lazily on first use and shared by all the clients of the process.
+///
+/// The clients use the [global](SharedResources::global) registry, so spinning up many clients
+/// loads every tokenizer only once.
+#[derive(Default)]
+pub struct SharedResources {
+    tokenizers: Mutex<HashMap<Tokenizer, Arc<CoreBPE>>>,
+}
+
+impl SharedResources {
+    /// Registry shared by the clients of the process.
+    pub fn global() -> &'static SharedResources {
+        static GLOBAL: OnceLock<SharedResources> = OnceLock::new();
+
+        GLOBAL.get_or_init(SharedResources::default)
+    }
+
+    /// Tokenizer of the `encoding`, loaded on first use.
+    ///
+    /// The clients count tokens with `o200k_base`; loading it in advance saves the time
+    /// from the first client creation.
+    pub fn tokenizer(&self, encoding: Tokenizer) -> Result<Arc<CoreBPE>, TokenizerInitError> {
+        // Holding the lock while loading makes concurrent callers wait for one tokenizer.
+        let mut tokenizers = self.tokenizers.lock().expect("not poisoned");
+
+        if let Some(tokenizer) = tokenizers.get(&encoding) {
+            return Ok(Arc::clone(tokenizer));
+        }
+
+        let tokenizer = Arc::new(
+            tiktoken_rs::get_bpe_from_tokenizer(encoding)
+                .map_err(|e| TokenizerInitError(e.to_string()))?,
+        );
+        tokenizers.insert(encoding, Arc::clone(&tokenizer));
+
+        Ok(tokenizer)
+    }
+
+    /// Tokenizer of the chat models, `o200k_base`.
+    pub(crate) fn chat_tokenizer(&self) -> Result<Arc<CoreBPE>, TokenizerInitError> {
+        self.tokenizer(Tokenizer::O200kBase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizers_are_shared() {
+        let resources = SharedResources::default();
+        let first = resources.tokenizer(Tokenizer::O200kBase).unwrap();
+        let second = resources.chat_tokenizer().unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(
+            &first,
+            &resources.tokenizer(Tokenizer::Cl100kBase).unwrap()
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Durable storage of the conversation history.
+
+#[cfg(feature = "sqlite")]
+use std::{path::Path, sync::Mutex};
+
+/// Storage of the request-response pairs of a conversation outside of memory.
+///
+/// The stored history is loaded when the [`ChatClient`](crate::ChatClient) is created, and
+/// every exchange extending the context is appended to the store as it happens. The store
+/// keeps the full history: pairs discarded from the context to fit the token limits are
+/// not removed.
+pub trait ContextStore: Send + Sync {
+    /// Stored request-response pairs, oldest first.
+    fn load(&self) -> Result<Vec<(String, String)>, Error>;
+
+    /// Append a request-response pair.
+    fn push(&self, request: &str, response: &str) -> Result<(), Error>;
+}
+
+/// [`ContextStore`] keeping the conversations in an SQLite database.
+///
+/// Several conversations, e.g., of different users of a bot, can share a database file, each
+/// identified by its conversation ID.
+#[cfg(feature = "sqlite")]
+pub struct SqliteContextStore {
+    connection: Mutex<rusqlite::Connection>,
+    conversation_id: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteContextStore {
+    /// Open the database at `path` and use the conversation `conversation_id` in it.
+    /// The database is created if missing.
+    pub fn open(path: impl AsRef<Path>, conversation_id: impl Into<String>) -> Result<Self, Error> {
+        Self::new(rusqlite::Connection::open(path)?, conversation_id.into())
+    }
+
+    /// Create the store in an in-memory database, e.g., for testing.
+    pub fn open_in_memory(conversation_id: impl Into<String>) -> Result<Self, Error> {
+        Self::new(
+            rusqlite::Connection::open_in_memory()?,
+            conversation_id.into(),
+        )
+    }
+
+    fn new(connection: rusqlite::Connection, conversation_id: String) -> Result<Self, Error> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS exchanges (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                conversation_id TEXT NOT NULL,
+                request TEXT NOT NULL,
+                response TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS exchanges_conversation_id
+                ON exchanges (conversation_id, id);",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            conversation_id,
+        })
+    }
+
+    /// ID of the conversation in the database.
+    pub fn conversation_id(&self) -> &str {
+        &self.conversation_id
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ContextStore for SqliteContextStore {
+    fn load(&self) -> Result<Vec<(String, String)>, Error> {
+        let connection = self.connection.lock().expect("not poisoned");
+        let mut statement = connection.prepare(
+            "SELECT request, response FROM exchanges WHERE conversation_id = ?1 ORDER BY id",
+        )?;
+
+        let exchanges = statement
+            .query_map([&self.conversation_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(exchanges)
+    }
+
+    fn push(&self, request: &str, response: &str) -> Result<(), Error> {
+        self.connection.lock().expect("not poisoned").execute(
+            "INSERT INTO exchanges (conversation_id, request, response) VALUES (?1, ?2, ?3)",
+            (&self.conversation_id, request, response),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Errors of the context stores.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// SQLite error.
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    /// Error of a custom store.
+    #[error("{0}")]
+    Custom(String),
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqlite_conversations() {
+        let path = std::env::temp_dir().join(format!("jutella-store-{}.db", std::process::id()));
+
+        let alice = SqliteContextStore::open(&path, "alice").unwrap();
+        alice.push("Hi", "Hello!").unwrap();
+        alice.push("How are you?", "Fine.").unwrap();
+
+        let bob = SqliteContextStore::open(&path, "bob").unwrap();
+        bob.push("Hey", "Hi!").unwrap();
+
+        let alice = SqliteContextStore::open(&path, "alice").unwrap();
+        assert_eq!(
+            alice.load().unwrap(),
+            vec![
+                (String::from("Hi"), String::from("Hello!")),
+                (String::from("How are you?"), String::from("Fine.")),
+            ]
+        );
+        assert_eq!(bob.load().unwrap().len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
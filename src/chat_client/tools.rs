@@ -0,0 +1,187 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Tool (function) calling.
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, value::Value};
+
+/// Tool the model can call, described to the model by its name, description and
+/// the JSON schema of its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tool {
+    /// Function name.
+    pub name: String,
+    /// What the function does, used by the model to choose when and how to call it.
+    pub description: Option<String>,
+    /// JSON schema of the function arguments.
+    pub parameters: Value,
+}
+
+impl Tool {
+    /// Create a new tool definition.
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
+        Self {
+            name: name.into(),
+            description: Some(description.into()),
+            parameters,
+        }
+    }
+
+    /// Tool definition in the chat completions request format.
+    pub(crate) fn to_json(&self) -> Value {
+        let mut function = json!({
+            "name": self.name,
+            "parameters": self.parameters,
+        });
+        if let Some(description) = &self.description {
+            function["description"] = json!(description);
+        }
+
+        json!({
+            "type": "function",
+            "function": function,
+        })
+    }
+}
+
+/// Tool call requested by the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCall {
+    /// Tool call ID, to be passed back with the result in [`ToolResult::call_id`].
+    pub id: String,
+    /// Name of the function to call.
+    pub name: String,
+    /// Function arguments as JSON generated by the model. Not guaranteed to be valid JSON
+    /// or to match the schema.
+    pub arguments: String,
+}
+
+impl ToolCall {
+    /// Parse the arguments into `T`.
+    pub fn parse_arguments<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_str(&self.arguments)
+    }
+}
+
+/// Result of a [`ToolCall`] to pass back to the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolResult {
+    /// ID of the tool call this is the result of.
+    pub call_id: String,
+    /// Result of the call, e.g., JSON or plain text.
+    pub content: String,
+}
+
+/// `tool_calls` field of the assistant message.
+#[derive(Deserialize)]
+struct RawToolCall {
+    id: String,
+    function: RawFunctionCall,
+}
+
+#[derive(Deserialize)]
+struct RawFunctionCall {
+    name: String,
+    arguments: String,
+}
+
+/// Parse the `tool_calls` field of the assistant message.
+pub(crate) fn parse_tool_calls(tool_calls: Option<&Value>) -> Result<Vec<ToolCall>, String> {
+    let Some(tool_calls) = tool_calls else {
+        return Ok(Vec::new());
+    };
+
+    Vec::<RawToolCall>::deserialize(tool_calls)
+        .map(|calls| {
+            calls
+                .into_iter()
+                .map(|RawToolCall { id, function }| ToolCall {
+                    id,
+                    name: function.name,
+                    arguments: function.arguments,
+                })
+                .collect()
+        })
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_definition() {
+        let tool = Tool::new(
+            "get_weather",
+            "Current weather in the city",
+            json!({ "type": "object", "properties": { "city": { "type": "string" } } }),
+        );
+
+        assert_eq!(
+            tool.to_json(),
+            json!({
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather in the city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "city": { "type": "string" } },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn tool_calls() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Arguments {
+            city: String,
+        }
+
+        let calls = parse_tool_calls(Some(&json!([{
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "get_weather", "arguments": "{\"city\":\"Berlin\"}" },
+        }])))
+        .unwrap();
+
+        assert_eq!(
+            calls,
+            vec![ToolCall {
+                id: String::from("call_1"),
+                name: String::from("get_weather"),
+                arguments: String::from("{\"city\":\"Berlin\"}"),
+            }]
+        );
+        assert_eq!(
+            calls[0].parse_arguments::<Arguments>().unwrap(),
+            Arguments {
+                city: String::from("Berlin")
+            }
+        );
+        assert_eq!(parse_tool_calls(None).unwrap(), Vec::new());
+        assert!(parse_tool_calls(Some(&json!({ "id": "call_1" }))).is_err());
+    }
+}
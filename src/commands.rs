@@ -0,0 +1,363 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Registry of the built-in slash commands.
+
+use crate::plugins;
+
+/// Built-in command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Help,
+    Bg,
+    Jobs,
+    Result,
+    History,
+    Less,
+    Tokens,
+    Remember,
+    Memories,
+    Forget,
+    Good,
+    Bad,
+    Locale,
+    Stop,
+    Fav,
+    Favs,
+    Checkpoint,
+    Rollback,
+    Count,
+    Base64,
+    Cost,
+    Incognito,
+}
+
+/// Command description shown in `/help`.
+struct Spec {
+    command: Builtin,
+    name: &'static str,
+    aliases: &'static [&'static str],
+    /// Argument hint, required arguments in angle brackets.
+    args: &'static str,
+    description: &'static str,
+}
+
+impl Spec {
+    fn usage(&self) -> String {
+        if self.args.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.args)
+        }
+    }
+}
+
+const COMMANDS: [Spec; 22] = [
+    Spec {
+        command: Builtin::Help,
+        name: "help",
+        aliases: &["h"],
+        args: "",
+        description: "show this help",
+    },
+    Spec {
+        command: Builtin::Bg,
+        name: "bg",
+        aliases: &[],
+        args: "<prompt>",
+        description: "ask a question in the background on a fork of the conversation",
+    },
+    Spec {
+        command: Builtin::Jobs,
+        name: "jobs",
+        aliases: &[],
+        args: "",
+        description: "list background questions",
+    },
+    Spec {
+        command: Builtin::Result,
+        name: "result",
+        aliases: &[],
+        args: "<N> [merge]",
+        description: "show the answer to background question N, optionally merging it",
+    },
+    Spec {
+        command: Builtin::History,
+        name: "history",
+        aliases: &["hist"],
+        args: "",
+        description: "list the conversation, marking the messages to be dropped",
+    },
+    Spec {
+        command: Builtin::Less,
+        name: "less",
+        aliases: &["pager"],
+        args: "[last]",
+        description: "show the session transcript or the latest exchange in $PAGER",
+    },
+    Spec {
+        command: Builtin::Tokens,
+        name: "tokens",
+        aliases: &[],
+        args: "",
+        description: "show the tokens in the context by role",
+    },
+    Spec {
+        command: Builtin::Remember,
+        name: "remember",
+        aliases: &[],
+        args: "<fact>",
+        description: "save a fact for the system message of new sessions",
+    },
+    Spec {
+        command: Builtin::Memories,
+        name: "memories",
+        aliases: &["mem"],
+        args: "",
+        description: "list remembered facts",
+    },
+    Spec {
+        command: Builtin::Forget,
+        name: "forget",
+        aliases: &[],
+        args: "<N>",
+        description: "remove remembered fact N",
+    },
+    Spec {
+        command: Builtin::Good,
+        name: "good",
+        aliases: &[],
+        args: "[comment]",
+        description: "rate the latest exchange as good",
+    },
+    Spec {
+        command: Builtin::Bad,
+        name: "bad",
+        aliases: &[],
+        args: "[comment]",
+        description: "rate the latest exchange as bad",
+    },
+    Spec {
+        command: Builtin::Locale,
+        name: "locale",
+        aliases: &["lang"],
+        args: "[tag]",
+        description: "show or switch the locale of the answers",
+    },
+    Spec {
+        command: Builtin::Stop,
+        name: "stop",
+        aliases: &[],
+        args: "[add \"<sequence>\" | clear]",
+        description: "list, add or clear stop sequences",
+    },
+    Spec {
+        command: Builtin::Fav,
+        name: "fav",
+        aliases: &[],
+        args: "[prompt]",
+        description: "save the prompt or the latest one as a favorite",
+    },
+    Spec {
+        command: Builtin::Favs,
+        name: "favs",
+        aliases: &[],
+        args: "[N]",
+        description: "list favorite prompts, or resend favorite N",
+    },
+    Spec {
+        command: Builtin::Checkpoint,
+        name: "checkpoint",
+        aliases: &[],
+        args: "[name]",
+        description: "save the conversation as a checkpoint, or list the checkpoints",
+    },
+    Spec {
+        command: Builtin::Rollback,
+        name: "rollback",
+        aliases: &[],
+        args: "<name>",
+        description: "roll the conversation back to a checkpoint",
+    },
+    Spec {
+        command: Builtin::Count,
+        name: "count",
+        aliases: &[],
+        args: "<text>",
+        description: "count the tokens of the text locally",
+    },
+    Spec {
+        command: Builtin::Base64,
+        name: "b64",
+        aliases: &[],
+        args: "<file>",
+        description: "estimate the size of the file encoded in base64",
+    },
+    Spec {
+        command: Builtin::Cost,
+        name: "cost",
+        aliases: &[],
+        args: "[prompt]",
+        description: "estimate the tokens and the cost of sending the prompt",
+    },
+    Spec {
+        command: Builtin::Incognito,
+        name: "incognito",
+        aliases: &[],
+        args: "[on | off]",
+        description:
+            "show or switch the incognito mode: exchanges are not saved and soon forgotten",
+    },
+];
+
+/// Max edit distance of a typo to suggest a command for.
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// Parsed input line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    /// Built-in command with arguments.
+    Builtin(Builtin, &'a str),
+    /// Built-in command missing required arguments or given unexpected ones.
+    Usage(String),
+    /// Any other command, run as a plugin.
+    Plugin(&'a str, &'a str),
+    /// Prompt to the model.
+    Prompt,
+}
+
+/// Parse the input line.
+pub fn parse(line: &str) -> Line<'_> {
+    let Some((name, args)) = plugins::parse_command(line.trim()) else {
+        return Line::Prompt;
+    };
+
+    let Some(spec) = find(name) else {
+        return Line::Plugin(name, args);
+    };
+
+    let args_required = spec.args.starts_with('<');
+    if (args_required && args.is_empty()) || (spec.args.is_empty() && !args.is_empty()) {
+        return Line::Usage(format!("Usage: {}", spec.usage()));
+    }
+
+    Line::Builtin(spec.command, args)
+}
+
+/// Whether `name` is a built-in command or its alias.
+pub fn is_builtin(name: &str) -> bool {
+    find(name).is_some()
+}
+
+fn find(name: &str) -> Option<&'static Spec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name == name || spec.aliases.contains(&name))
+}
+
+/// Built-in command closest to the mistyped `name`, if any.
+pub fn suggest(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .map(|spec| (spec.name, edit_distance(name, spec.name)))
+        .filter(|(command, distance)| *distance <= MAX_TYPO_DISTANCE && *distance < command.len())
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(command, _)| command)
+}
+
+/// `/help` output.
+pub fn help() -> String {
+    let usage = COMMANDS.iter().map(Spec::usage).collect::<Vec<_>>();
+    let width = usage.iter().map(String::len).max().unwrap_or_default();
+
+    let mut help = String::from("Commands:\n");
+    for (spec, usage) in COMMANDS.iter().zip(usage) {
+        help += &format!("  {usage:width$}  {}", spec.description);
+        if !spec.aliases.is_empty() {
+            let aliases = spec
+                .aliases
+                .iter()
+                .map(|alias| format!("/{alias}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            help += &format!(" (alias {aliases})");
+        }
+        help += "\n";
+    }
+    help += "Any other /<command> [args] runs the plugin `jutella-<command>` from PATH.";
+
+    help
+}
+
+/// Levenshtein distance.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(parse("/jobs"), Line::Builtin(Builtin::Jobs, ""));
+        assert_eq!(parse("/mem"), Line::Builtin(Builtin::Memories, ""));
+        assert_eq!(
+            parse("/bg  what is Rust? "),
+            Line::Builtin(Builtin::Bg, "what is Rust?")
+        );
+        assert_eq!(
+            parse("/bg"),
+            Line::Usage(String::from("Usage: /bg <prompt>"))
+        );
+        assert_eq!(
+            parse("/tokens all"),
+            Line::Usage(String::from("Usage: /tokens"))
+        );
+        assert_eq!(
+            parse("/summarize short"),
+            Line::Plugin("summarize", "short")
+        );
+        assert_eq!(parse("/usr/bin/env?"), Line::Prompt);
+        assert_eq!(parse("hello"), Line::Prompt);
+    }
+
+    #[test]
+    fn typo_suggestions() {
+        assert_eq!(suggest("hisotry"), Some("history"));
+        assert_eq!(suggest("job"), Some("jobs"));
+        assert_eq!(suggest("summarize"), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Local estimates of `/count`, `/b64` and `/cost`, not sending anything to the API.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 encoding with padding, as used in data URLs.
+pub fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |triple, (i, byte)| {
+            triple | u32::from(*byte) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (triple >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Prices of the models by name.
+pub type Prices = BTreeMap<String, Price>;
+
+/// Prices of the tokens of a model in USD per million tokens.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Price {
+    pub input: Option<f64>,
+    pub output: Option<f64>,
+}
+
+impl Price {
+    /// Cost of the input and output tokens in USD, if both prices are known.
+    pub fn cost(&self, tokens_in: usize, tokens_out: usize) -> Option<f64> {
+        Some(cost(tokens_in, self.input?) + cost(tokens_out, self.output?))
+    }
+}
+
+/// Cost of `tokens` in USD at `price` USD per million tokens.
+pub fn cost(tokens: usize, price: f64) -> f64 {
+    tokens as f64 * price / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00, 0x01]), "//4AAQ==");
+        assert_eq!(cost(2_000_000, 0.15), 0.3);
+    }
+
+    #[test]
+    fn price_cost() {
+        let price = Price {
+            input: Some(0.5),
+            output: Some(2.0),
+        };
+        assert_eq!(price.cost(2_000_000, 500_000), Some(2.0));
+
+        let input_only = Price {
+            input: Some(0.5),
+            output: None,
+        };
+        assert_eq!(input_only.cost(2_000_000, 0), None);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Favorite prompts saved with `/fav` and resent with `/favs N`.
+
+use anyhow::Context as _;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Favorite prompts kept in a JSON file.
+#[derive(Debug)]
+pub struct Favorites {
+    path: PathBuf,
+    prompts: Vec<String>,
+}
+
+impl Favorites {
+    /// Load the favorites from `path`. A missing file is treated as no favorites.
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let prompts = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Invalid favorites file {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).context("Failed to read favorites"),
+        };
+
+        Ok(Self { path, prompts })
+    }
+
+    pub fn prompts(&self) -> &[String] {
+        &self.prompts
+    }
+
+    /// Favorite prompt number `number`, starting from 1.
+    pub fn get(&self, number: usize) -> Option<&str> {
+        number
+            .checked_sub(1)
+            .and_then(|index| self.prompts.get(index))
+            .map(String::as_str)
+    }
+
+    /// Add the prompt and save the favorites. A prompt already in the favorites, ignoring
+    /// surrounding whitespace, is not added twice. Returns the number of the prompt.
+    pub fn add(&mut self, prompt: &str) -> anyhow::Result<usize> {
+        let prompt = prompt.trim();
+
+        if let Some(index) = self.prompts.iter().position(|p| p == prompt) {
+            return Ok(index + 1);
+        }
+
+        self.prompts.push(prompt.to_string());
+        save(&self.path, &self.prompts).context("Failed to save favorites")?;
+
+        Ok(self.prompts.len())
+    }
+}
+
+fn save(path: &Path, prompts: &[String]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string_pretty(prompts)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_deduplicates() {
+        let path = std::env::temp_dir()
+            .join(format!("jutella-test-{}", std::process::id()))
+            .join("favorites.json");
+        let _ = fs::remove_file(&path);
+
+        let mut favorites = Favorites::load(path.clone()).unwrap();
+        assert_eq!(favorites.add("Explain this error").unwrap(), 1);
+        assert_eq!(favorites.add("Summarize").unwrap(), 2);
+        assert_eq!(favorites.add(" Explain this error\n").unwrap(), 1);
+
+        let favorites = Favorites::load(path.clone()).unwrap();
+        assert_eq!(favorites.prompts(), ["Explain this error", "Summarize"]);
+        assert_eq!(favorites.get(2), Some("Summarize"));
+        assert_eq!(favorites.get(0), None);
+
+        fs::remove_file(path).unwrap();
+    }
+}
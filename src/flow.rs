@@ -0,0 +1,81 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! `jutella flow`: guided conversation flows in the terminal.
+
+use crate::{print_info, print_prompt, print_response, read_line};
+use anyhow::Context as _;
+use colored::Colorize as _;
+use jutella::{ChatClient, Flow, FlowUser};
+use std::{fs, io, path::Path};
+
+/// Run the flow defined in the TOML or JSON file `path`.
+pub async fn run(mut chat: ChatClient, path: &Path, hyperlinks: bool) -> anyhow::Result<()> {
+    let definition =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let flow = if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        Flow::from_json(&definition)?
+    } else {
+        toml::from_str(&definition)?
+    };
+
+    let mut terminal = Terminal {
+        hyperlinks,
+        error: None,
+    };
+    let answers = flow.run(&mut chat, &mut terminal).await?;
+    if let Some(e) = terminal.error {
+        return Err(e).context("Failed to read the answer");
+    }
+
+    print_info(format!("Flow finished after {} steps.", answers.len()));
+
+    Ok(())
+}
+
+/// User answering the questions in the terminal.
+struct Terminal {
+    hyperlinks: bool,
+    /// Error reading the answer, which stopped the flow.
+    error: Option<io::Error>,
+}
+
+impl FlowUser for Terminal {
+    fn answer(&mut self, question: &str) -> Option<String> {
+        println!("\n{}\n", question.bold());
+
+        match print_prompt().and_then(|()| read_line()) {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn response(&mut self, _id: &str, response: &str) {
+        print_response(response, self.hyperlinks);
+    }
+}
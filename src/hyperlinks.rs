@@ -0,0 +1,148 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! OSC 8 terminal hyperlinks.
+
+use std::{
+    env,
+    io::{self, IsTerminal as _},
+};
+
+const URL_SCHEMES: [&str; 2] = ["https://", "http://"];
+
+/// Whether stdout is a terminal known to support OSC 8 hyperlinks.
+///
+/// `FORCE_HYPERLINK=1` / `FORCE_HYPERLINK=0` overrides the detection.
+pub fn supported() -> bool {
+    if let Ok(force) = env::var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+
+    if !io::stdout().is_terminal() {
+        return false;
+    }
+
+    let env_set = |name| env::var_os(name).is_some();
+    let env_is = |name, values: &[&str]| {
+        env::var(name).is_ok_and(|value| values.iter().any(|v| value.starts_with(v)))
+    };
+
+    env_set("WT_SESSION")
+        || env_set("KITTY_WINDOW_ID")
+        || env_set("KONSOLE_VERSION")
+        || env_set("DOMTERM")
+        || env_is(
+            "TERM_PROGRAM",
+            &["iTerm.app", "WezTerm", "vscode", "ghostty", "Hyper"],
+        )
+        || env_is(
+            "TERM",
+            &["xterm-kitty", "alacritty", "foot", "xterm-ghostty"],
+        )
+        || env::var("VTE_VERSION")
+            .ok()
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000)
+}
+
+/// Wrap URLs in the text into OSC 8 hyperlinks.
+pub fn linkify(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = URL_SCHEMES.iter().filter_map(|s| rest.find(s)).min() {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let len = url_len(rest);
+        let url = &rest[..len];
+        result.push_str(&format!("\x1b]8;;{url}\x1b\\{url}\x1b]8;;\x1b\\"));
+        rest = &rest[len..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Length of the URL at the start of the text, excluding trailing punctuation.
+fn url_len(text: &str) -> usize {
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`'))
+        .unwrap_or(text.len());
+    let mut url = &text[..end];
+
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_']);
+        let trimmed = match trimmed.chars().last() {
+            Some(')') if trimmed.matches('(').count() < trimmed.matches(')').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            Some(']') if trimmed.matches('[').count() < trimmed.matches(']').count() => {
+                &trimmed[..trimmed.len() - 1]
+            }
+            _ => trimmed,
+        };
+
+        if trimmed.len() == url.len() {
+            break url.len();
+        }
+        url = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str) -> String {
+        format!("\x1b]8;;{url}\x1b\\{url}\x1b]8;;\x1b\\")
+    }
+
+    #[test]
+    fn no_urls() {
+        assert_eq!(linkify("plain text"), "plain text");
+    }
+
+    #[test]
+    fn urls_in_text() {
+        assert_eq!(
+            linkify("See https://example.com/a, or http://example.org."),
+            format!(
+                "See {}, or {}.",
+                link("https://example.com/a"),
+                link("http://example.org")
+            ),
+        );
+    }
+
+    #[test]
+    fn markdown_links() {
+        assert_eq!(
+            linkify("[docs](https://docs.rs/jutella) and (https://en.wikipedia.org/wiki/Rust_(language))"),
+            format!(
+                "[docs]({}) and ({})",
+                link("https://docs.rs/jutella"),
+                link("https://en.wikipedia.org/wiki/Rust_(language)")
+            ),
+        );
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Background requests running on forked conversations.
+
+use crate::print_prompt;
+use anyhow::anyhow;
+use colored::Colorize as _;
+use jutella::ChatClient;
+use tokio::task::JoinHandle;
+
+/// Background request.
+struct Job {
+    prompt: String,
+    state: JobState,
+    /// Whether the exchange was merged into the conversation.
+    merged: bool,
+}
+
+enum JobState {
+    Running(JoinHandle<Result<String, jutella::Error>>),
+    Finished(Result<String, String>),
+}
+
+/// Background requests started during the session.
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    /// Ask a question on a fork of the conversation in the background. Returns the job number.
+    pub fn spawn(&mut self, chat: &ChatClient, prompt: String) -> usize {
+        let number = self.jobs.len() + 1;
+        let mut fork = chat.fork();
+        let request = prompt.clone();
+
+        let handle = tokio::spawn(async move {
+            let result = fork.ask(request).await;
+
+            println!(
+                "\n{}",
+                format!("Background job {number} finished, see `/result {number}`.").cyan()
+            );
+            print_prompt().unwrap_or_default();
+
+            result
+        });
+
+        self.jobs.push(Job {
+            prompt,
+            state: JobState::Running(handle),
+            merged: false,
+        });
+
+        number
+    }
+
+    /// Job numbers, prompts and statuses.
+    pub fn list(&self) -> impl Iterator<Item = (usize, &str, &'static str)> {
+        self.jobs.iter().enumerate().map(|(index, job)| {
+            let status = match &job.state {
+                JobState::Running(handle) if !handle.is_finished() => "running",
+                _ if job.merged => "merged",
+                _ => "finished",
+            };
+
+            (index + 1, job.prompt.as_str(), status)
+        })
+    }
+
+    /// Prompt and response of a finished job. With `merge`, the job is marked as merged, and
+    /// merging it again is refused so the exchange is not duplicated.
+    pub async fn result(&mut self, number: usize, merge: bool) -> anyhow::Result<(&str, &str)> {
+        let job = number
+            .checked_sub(1)
+            .and_then(|index| self.jobs.get_mut(index))
+            .ok_or(anyhow!("No background job {number}"))?;
+
+        if let JobState::Running(handle) = &mut job.state {
+            if !handle.is_finished() {
+                return Err(anyhow!("Background job {number} is still running"));
+            }
+
+            let result = handle
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result.map_err(|e| e.to_string()));
+            job.state = JobState::Finished(result);
+        }
+
+        if merge && job.merged {
+            return Err(anyhow!(
+                "Background job {number} is already merged into the conversation"
+            ));
+        }
+
+        match &job.state {
+            JobState::Finished(Ok(response)) => {
+                job.merged |= merge;
+                Ok((&job.prompt, response))
+            }
+            JobState::Finished(Err(error)) => {
+                Err(anyhow!("Background job {number} failed: {error}"))
+            }
+            JobState::Running(_) => unreachable!("job state is updated above"),
+        }
+    }
+}
@@ -26,6 +26,36 @@
 
 mod chat_client;
 pub use chat_client::{
-    client::{ChatClient, ChatClientConfig, Completion, Error},
-    openai_api::client::Auth,
+    client::{
+        AbortHandle, AuthRefresh, BestOf, ChatClient, ChatClientConfig, Completion, Error, Event,
+        ExchangeHook, ExchangeSummary, JudgeConfig, RequestOptions, SafetyInfo,
+        SystemMessageProvider, TurnPreview, Warning,
+    },
+    context::{
+        ContextBuilder, ContextStrategy, Error as ContextError, Exchange, Feedback, InitialContext,
+        Rating, TokenBreakdown,
+    },
+    flow::{Branch, Error as FlowError, Flow, FlowUser, Step, END as FLOW_END},
+    logit_bias::{Error as LogitBiasError, LogitBias},
+    memory::{Error as MemoryError, MemoryStore},
+    observer::{Observer, TokenUsage, Turn},
+    ollama_api::OllamaOptions,
+    openai_api::{
+        chat_completions::{
+            JsonSchemaBuilder, Logprobs, Modality, OutOfRangeError, ReasoningEffort,
+            ResponseFormat, Temperature, TokenLogprob, TopLogprob, TopP,
+        },
+        client::{Auth, Proxy, RetryPolicy, Tls},
+        entra_id::EntraIdCredential,
+        message::Image,
+        rate_limit::RateLimits,
+    },
+    provider::Provider,
+    segments::Segment,
+    shared::{SharedResources, TokenizerInitError},
+    store::{ContextStore, Error as StoreError},
+    tools::{Tool, ToolCall, ToolResult},
 };
+
+#[cfg(feature = "sqlite")]
+pub use chat_client::store::SqliteContextStore;
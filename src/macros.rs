@@ -0,0 +1,162 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Custom slash commands defined in the `[commands]` tables of the config.
+
+use crate::{commands, plugins};
+use anyhow::anyhow;
+use jutella::{ReasoningEffort, RequestOptions, Temperature};
+use std::collections::BTreeMap;
+
+/// Placeholder of the command arguments in the prompt template.
+const INPUT: &str = "{input}";
+
+/// Custom commands by name.
+pub type Macros = BTreeMap<String, Macro>;
+
+/// Custom command `/<name> [input]` sending a prompt built from a template.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Macro {
+    /// Prompt template, `{input}` is replaced with the command arguments. The arguments are
+    /// appended to the prompt if the template has no placeholder.
+    prompt: String,
+    /// Append the clipboard contents to the prompt.
+    #[serde(default)]
+    attach_clipboard: bool,
+    /// Description shown in `/help`.
+    description: Option<String>,
+    temperature: Option<Temperature>,
+    max_completion_tokens: Option<usize>,
+    reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl Macro {
+    pub fn attach_clipboard(&self) -> bool {
+        self.attach_clipboard
+    }
+
+    /// Prompt with the `input` and the `clipboard` contents, if any.
+    pub fn prompt(&self, input: &str, clipboard: Option<&str>) -> String {
+        let mut prompt = if self.prompt.contains(INPUT) {
+            self.prompt.replace(INPUT, input)
+        } else if input.is_empty() {
+            self.prompt.clone()
+        } else {
+            format!("{}\n\n{input}", self.prompt)
+        };
+
+        if let Some(clipboard) = clipboard {
+            prompt = format!("{prompt}\n\n{clipboard}");
+        }
+
+        prompt
+    }
+
+    /// Request parameters of the command overriding the configured ones.
+    pub fn options(&self) -> RequestOptions {
+        RequestOptions {
+            temperature: self.temperature,
+            max_completion_tokens: self.max_completion_tokens,
+            reasoning_effort: self.reasoning_effort,
+            ..Default::default()
+        }
+    }
+}
+
+/// Check that the commands have valid names not shadowing the built-in commands.
+pub fn validate(macros: &Macros) -> anyhow::Result<()> {
+    for name in macros.keys() {
+        if plugins::parse_command(&format!("/{name}")) != Some((name.as_str(), "")) {
+            return Err(anyhow!(
+                "Invalid command name `{name}`: use letters, digits, `-` and `_`"
+            ));
+        }
+        if commands::is_builtin(name) {
+            return Err(anyhow!("Command `/{name}` shadows the built-in command"));
+        }
+    }
+
+    Ok(())
+}
+
+/// `/help` section listing the custom commands, if any.
+pub fn help(macros: &Macros) -> Option<String> {
+    if macros.is_empty() {
+        return None;
+    }
+
+    let usage = macros
+        .keys()
+        .map(|name| format!("/{name} [input]"))
+        .collect::<Vec<_>>();
+    let width = usage.iter().map(String::len).max().unwrap_or_default();
+
+    let mut help = String::from("Custom commands:");
+    for (command, usage) in macros.values().zip(usage) {
+        let description = command.description.as_deref().unwrap_or(&command.prompt);
+        help += &format!("\n  {usage:width$}  {}", crate::summary(description));
+    }
+
+    Some(help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(prompt: &str) -> Macro {
+        Macro {
+            prompt: prompt.to_string(),
+            attach_clipboard: false,
+            description: None,
+            temperature: None,
+            max_completion_tokens: None,
+            reasoning_effort: None,
+        }
+    }
+
+    #[test]
+    fn prompt_expansion() {
+        let review = command("Review this code for bugs: {input}");
+        assert_eq!(
+            review.prompt("main.rs", Some("fn main() {}")),
+            "Review this code for bugs: main.rs\n\nfn main() {}"
+        );
+
+        let translate = command("Translate to German.");
+        assert_eq!(
+            translate.prompt("Good morning", None),
+            "Translate to German.\n\nGood morning"
+        );
+        assert_eq!(translate.prompt("", None), "Translate to German.");
+    }
+
+    #[test]
+    fn name_validation() {
+        let macros = |name: &str| Macros::from([(name.to_string(), command("{input}"))]);
+
+        assert!(validate(&macros("review")).is_ok());
+        assert!(validate(&macros("help")).is_err());
+        assert!(validate(&macros("code review")).is_err());
+    }
+}
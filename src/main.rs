@@ -23,61 +23,848 @@
 //! CLI interface for `jutella`.
 
 mod app_config;
-use app_config::{Args, Configuration};
+mod benchmark;
+mod bundle;
+mod commands;
+mod estimate;
+mod favorites;
+mod feedback;
+mod flow;
+mod hyperlinks;
+mod jobs;
+mod macros;
+mod plugins;
+mod preferences;
+mod webhook;
+use app_config::{Args, CliCommand, Configuration, ModelsCommand};
+use commands::{Builtin, Line};
+use estimate::Price;
+use favorites::Favorites;
+use feedback::Feedback;
+use jobs::Jobs;
+use macros::Macros;
+use plugins::Output;
+use preferences::Preferences;
 
 use anyhow::{anyhow, Context as _};
 use colored::Colorize as _;
-use jutella::{ChatClient, ChatClientConfig};
+use jutella::{ChatClient, ChatClientConfig, Event, MemoryStore, Rating, RequestOptions, Warning};
 use std::{
+    env, fs,
     io::{self, Read as _, Write as _},
+    path::PathBuf,
     process::{Command, Stdio},
+    sync::{Arc, Mutex},
 };
+use tokio::sync::broadcast::{self, error::TryRecvError};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let mut args = Args::parse();
+
+    if let Some(command) = args.take_config_command() {
+        return bundle::run(command, &args.config_path()?);
+    }
+
     let Configuration {
+        provider,
         auth,
         api_version,
         api_url,
+        fallback_api_urls,
+        retry_policy,
+        rate_limits,
+        connect_timeout,
+        proxy,
+        tls,
+        headers,
         model,
+        validate_model,
+        temperature,
+        top_p,
+        max_completion_tokens,
+        frequency_penalty,
+        presence_penalty,
+        seed,
+        response_format,
         system_message,
+        preferences,
         xclip,
         min_history_tokens,
         max_history_tokens,
-    } = Configuration::init(Args::parse())?;
+        context_strategy,
+        confirm_above_tokens,
+        incognito_turns,
+        prices,
+        memories_file,
+        benchmarks_file,
+        feedback_file,
+        favorites_file,
+        hyperlinks,
+        webhook_url,
+        webhook_errors_only,
+        ollama_options,
+        macros,
+        command,
+    } = Configuration::init(args)?;
+
+    let price = prices.get(&model).copied().unwrap_or_default();
+
+    let flow_file = match &command {
+        Some(CliCommand::Flow { file }) => Some(file.clone()),
+        _ => None,
+    };
+
+    if let Some(CliCommand::Models {
+        command: ModelsCommand::Benchmark { models, runs },
+    }) = command
+    {
+        let models = if models.is_empty() {
+            vec![model]
+        } else {
+            models
+        };
+        let clients = models
+            .into_iter()
+            .map(|model| {
+                let chat = ChatClient::new(
+                    auth.clone(),
+                    ChatClientConfig {
+                        api_url: api_url.clone(),
+                        api_version: api_version.clone(),
+                        fallback_api_urls: fallback_api_urls.clone(),
+                        connect_timeout,
+                        proxy: proxy.clone(),
+                        tls: tls.clone(),
+                        headers: headers.clone(),
+                        model: model.clone(),
+                        provider,
+                        ollama_options: ollama_options.clone(),
+                        ..Default::default()
+                    },
+                )?;
+                Ok((model, chat))
+            })
+            .collect::<Result<Vec<_>, jutella::Error>>()
+            .context("Failed to initialize the client")?;
+
+        return benchmark::run(clients, runs, &prices, &benchmarks_file).await;
+    }
+
+    let memories = MemoryStore::load(memories_file).context("Failed to load memories")?;
+    let favorites = Favorites::load(favorites_file)?;
+
+    // Preferences can be switched during the session, so the system message is evaluated
+    // for every request.
+    let system_message = memories.system_message(system_message);
+    let preferences = Arc::new(Mutex::new(preferences));
+    let system_message_provider = {
+        let preferences = Arc::clone(&preferences);
+        Arc::new(move || {
+            preferences
+                .lock()
+                .expect("not poisoned")
+                .system_message(system_message.clone())
+                .unwrap_or_default()
+        })
+    };
 
     let mut chat = ChatClient::new(
         auth,
         ChatClientConfig {
             api_url,
             api_version,
+            fallback_api_urls,
+            retry_policy,
+            rate_limits,
+            connect_timeout,
+            proxy,
+            tls,
+            headers,
             model,
-            system_message,
+            temperature,
+            top_p,
+            max_completion_tokens,
+            frequency_penalty,
+            presence_penalty,
+            seed,
+            response_format,
+            provider,
+            system_message_provider: Some(system_message_provider),
             min_history_tokens,
             max_history_tokens,
+            context_strategy,
+            ollama_options,
+            on_exchange: webhook_url.map(|url| webhook::hook(url, webhook_errors_only)),
+            ..Default::default()
         },
     )
     .context("Failed to initialize the client")?;
 
+    if validate_model {
+        chat.validate_model()
+            .await
+            .context("Failed to validate the model")?;
+    }
+
+    if let Some(flow_file) = flow_file {
+        return flow::run(chat, &flow_file, hyperlinks && hyperlinks::supported()).await;
+    }
+
+    let mut session = Session {
+        events: chat.subscribe(),
+        chat,
+        jobs: Jobs::default(),
+        memories,
+        favorites,
+        last_prompt: None,
+        preferences,
+        transcript: Vec::new(),
+        feedback_file,
+        confirm_above_tokens,
+        price,
+        xclip,
+        hyperlinks: hyperlinks && hyperlinks::supported(),
+        macros,
+        incognito_turns,
+    };
+
     print_prompt()?;
 
-    for line in io::stdin().lines() {
-        if let Ok(response) = chat.ask(line?).await.inspect_err(|e| print_error(e)) {
-            print_response(&response);
+    // Lines are read one by one without locking stdin, as commands may read confirmations.
+    while let Some(line) = read_line()? {
+        session.handle_line(line).await;
+        session.print_warnings();
+        print_prompt()?;
+    }
+
+    println!();
+
+    Ok(())
+}
 
-            if xclip {
+/// Interactive CLI session.
+struct Session {
+    chat: ChatClient,
+    events: broadcast::Receiver<Event>,
+    jobs: Jobs,
+    memories: MemoryStore,
+    favorites: Favorites,
+    /// Latest prompt sent to the model, for `/fav`.
+    last_prompt: Option<String>,
+    preferences: Arc<Mutex<Preferences>>,
+    /// Formatted exchanges of the session, including the ones discarded from the context.
+    transcript: Vec<String>,
+    feedback_file: PathBuf,
+    confirm_above_tokens: Option<usize>,
+    /// Prices of the configured model.
+    price: Price,
+    xclip: bool,
+    hyperlinks: bool,
+    /// Custom commands from the config.
+    macros: Macros,
+    /// Number of the following requests incognito exchanges stay in the context for.
+    incognito_turns: usize,
+}
+
+impl Session {
+    async fn handle_line(&mut self, line: String) {
+        let result = match commands::parse(&line) {
+            Line::Builtin(command, args) => self.run_command(command, args).await,
+            Line::Usage(usage) => Err(anyhow!(usage)),
+            Line::Plugin(name, args) if self.macros.contains_key(name) => {
+                self.run_macro(name, args).await
+            }
+            Line::Plugin(name, args) => match commands::suggest(name) {
+                Some(command) if !plugins::is_installed(name) => Err(anyhow!(
+                    "Unknown command `/{name}`, did you mean `/{command}`? See `/help`."
+                )),
+                _ => self.run_plugin(name, args).await,
+            },
+            Line::Prompt => {
+                self.ask(line).await;
+                Ok(())
+            }
+        };
+
+        result.inspect_err(|e| print_error(e)).unwrap_or_default();
+    }
+
+    async fn run_command(&mut self, command: Builtin, args: &str) -> anyhow::Result<()> {
+        match command {
+            Builtin::Help => {
+                print_info(commands::help());
+                if let Some(help) = macros::help(&self.macros) {
+                    print_info(help);
+                }
+            }
+            Builtin::Bg => {
+                let number = self.jobs.spawn(&self.chat, args.to_string());
+                print_info(format!("Started background job {number}."));
+            }
+            Builtin::Jobs => self.print_jobs(),
+            Builtin::Result => self.show_result(args).await?,
+            Builtin::History => self.print_history(),
+            Builtin::Less => self.page(args)?,
+            Builtin::Tokens => self.print_tokens()?,
+            Builtin::Remember => self.remember(args.to_string())?,
+            Builtin::Memories => self.print_memories(),
+            Builtin::Forget => self.forget(args)?,
+            Builtin::Good => self.rate(Rating::Good, args)?,
+            Builtin::Bad => self.rate(Rating::Bad, args)?,
+            Builtin::Locale => self.locale(args),
+            Builtin::Stop => self.stop(args)?,
+            Builtin::Fav => self.add_favorite(args)?,
+            Builtin::Favs => self.favorites(args).await?,
+            Builtin::Checkpoint => self.checkpoint(args),
+            Builtin::Rollback => self.rollback(args)?,
+            Builtin::Count => self.count(args)?,
+            Builtin::Base64 => self.base64_size(args)?,
+            Builtin::Cost => self.cost(args)?,
+            Builtin::Incognito => self.incognito(args)?,
+        }
+
+        Ok(())
+    }
+
+    async fn ask(&mut self, prompt: String) {
+        self.ask_with(prompt, RequestOptions::default()).await
+    }
+
+    async fn ask_with(&mut self, prompt: String, options: RequestOptions) {
+        match self.confirm_send(&prompt) {
+            Ok(true) => {}
+            Ok(false) => {
+                print_info("Not sent.");
+                return;
+            }
+            Err(e) => {
+                print_error(e);
+                return;
+            }
+        }
+
+        // Incognito exchanges are kept out of the transcript, favorites and clipboard.
+        let incognito = self.chat.ephemeral().is_some();
+        if !incognito {
+            self.last_prompt = Some(prompt.clone());
+        }
+
+        if let Ok(response) = self
+            .chat
+            .ask_with(prompt.clone(), options)
+            .await
+            .inspect_err(|e| print_error(e))
+        {
+            self.print_response(&response);
+            if incognito {
+                return;
+            }
+
+            self.transcript
+                .push(format_exchange(&prompt, &response, self.hyperlinks));
+
+            if self.xclip {
                 copy_to_clipboard(response)
                     .inspect_err(|e| print_error(e))
                     .unwrap_or_default();
             }
         }
+    }
 
-        print_prompt()?;
+    /// Ask for confirmation if the prompt exceeds `confirm_above_tokens`.
+    fn confirm_send(&self, prompt: &str) -> anyhow::Result<bool> {
+        let Some(threshold) = self.confirm_above_tokens else {
+            return Ok(true);
+        };
+
+        let tokens = self.chat.estimate_prompt_tokens(prompt)?;
+        if tokens <= threshold {
+            return Ok(true);
+        }
+
+        let cost = self
+            .price
+            .input
+            .map(|price| format!(" (${:.4})", estimate::cost(tokens, price)))
+            .unwrap_or_default();
+        print!(
+            "{} ",
+            format!("The prompt is about {tokens} tokens{cost}. Send? [y/N]").yellow()
+        );
+        io::stdout().flush()?;
+
+        let answer = read_line()?.unwrap_or_default();
+
+        Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
     }
 
-    println!();
+    async fn run_plugin(&mut self, name: &str, args: &str) -> anyhow::Result<()> {
+        match plugins::run(name, args, self.chat.history())? {
+            Output::Prompt(prompt) => {
+                println!("{} {prompt}", format!("/{name}:").bold().red());
+                self.ask(prompt).await;
+            }
+            Output::Display(message) => print_info(message),
+            Output::Attachment(path) => {
+                return Err(anyhow!(
+                    "Plugin `/{name}` returned attachment `{path}`, but attachments are not supported"
+                ));
+            }
+        }
 
-    Ok(())
+        Ok(())
+    }
+
+    /// Send the prompt of the custom command `name` with the `input`.
+    async fn run_macro(&mut self, name: &str, input: &str) -> anyhow::Result<()> {
+        let command = &self.macros[name];
+        let clipboard = command
+            .attach_clipboard()
+            .then(paste_from_clipboard)
+            .transpose()?;
+        let prompt = command.prompt(input, clipboard.as_deref());
+        let options = command.options();
+
+        println!("{} {}", format!("/{name}:").bold().red(), summary(&prompt));
+        self.ask_with(prompt, options).await;
+
+        Ok(())
+    }
+
+    /// Print the warnings reported by the client, including by background jobs.
+    fn print_warnings(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(Event::Warning(warning)) => print_warning(warning),
+                Ok(_) | Err(TryRecvError::Lagged(_)) => continue,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn print_response(&self, response: &str) {
+        print_response(response, self.hyperlinks);
+    }
+
+    fn print_jobs(&self) {
+        let mut empty = true;
+
+        for (number, prompt, status) in self.jobs.list() {
+            print_info(format!("[{number}] {status}: {prompt}"));
+            empty = false;
+        }
+
+        if empty {
+            print_info("No background jobs.");
+        }
+    }
+
+    fn print_history(&self) {
+        let history = self.chat.eviction_preview();
+
+        if history.is_empty() {
+            print_info("The conversation is empty.");
+        }
+
+        for (index, turn) in history.into_iter().enumerate() {
+            let tokens = turn
+                .tokens
+                .map(|tokens| format!(" ({tokens} tokens)"))
+                .unwrap_or_default();
+            let marker = if turn.evicted {
+                " [dropped on next request]".yellow().to_string()
+            } else {
+                String::new()
+            };
+
+            println!(
+                "{}{}{marker}\n  {}",
+                format!("[{}]", index + 1).bold(),
+                tokens,
+                summary(turn.request),
+            );
+        }
+    }
+
+    /// Show the session transcript in `$PAGER`, or only the latest exchange with `/less last`.
+    fn page(&self, args: &str) -> anyhow::Result<()> {
+        let transcript = match args.trim() {
+            "" => self.transcript.concat(),
+            "last" => self
+                .transcript
+                .last()
+                .cloned()
+                .context("The conversation is empty")?,
+            _ => return Err(anyhow!("Usage: /less [last]")),
+        };
+
+        if transcript.is_empty() {
+            return Err(anyhow!("The conversation is empty"));
+        }
+
+        run_pager(transcript)
+    }
+
+    fn print_tokens(&self) -> anyhow::Result<()> {
+        let tokens = self.chat.context_stats()?;
+
+        print_info(format!(
+            "System: {}, user: {}, assistant: {}, total: {} tokens.",
+            tokens.system,
+            tokens.user,
+            tokens.assistant,
+            tokens.total(),
+        ));
+
+        Ok(())
+    }
+
+    /// Show the result of a background job, merging it into the conversation if requested
+    /// with `/result N merge`.
+    async fn show_result(&mut self, args: &str) -> anyhow::Result<()> {
+        let mut args = args.split_whitespace();
+        let number = args
+            .next()
+            .and_then(|n| n.parse().ok())
+            .context("Usage: /result N [merge]")?;
+        let merge = match args.next() {
+            None => false,
+            Some("merge") => true,
+            Some(_) => return Err(anyhow!("Usage: /result N [merge]")),
+        };
+
+        let hyperlinks = self.hyperlinks;
+        let (prompt, response) = self.jobs.result(number, merge).await?;
+        print_response(response, hyperlinks);
+
+        if merge {
+            self.transcript
+                .push(format_exchange(prompt, response, hyperlinks));
+            self.chat
+                .push_exchange(prompt.to_string(), response.to_string());
+            print_info(format!(
+                "Merged background job {number} into the conversation."
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn remember(&mut self, fact: String) -> anyhow::Result<()> {
+        if fact.is_empty() {
+            return Err(anyhow!("Usage: /remember <fact>"));
+        }
+
+        self.memories.remember(fact)?;
+        print_info("Remembered. The fact will be used in new sessions.");
+
+        Ok(())
+    }
+
+    fn print_memories(&self) {
+        if self.memories.facts().is_empty() {
+            print_info("No memories.");
+        }
+
+        for (index, fact) in self.memories.facts().iter().enumerate() {
+            print_info(format!("[{}] {fact}", index + 1));
+        }
+    }
+
+    fn forget(&mut self, number: &str) -> anyhow::Result<()> {
+        let index = number
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .context("Usage: /forget N")?;
+
+        let fact = self.memories.forget(index)?;
+        print_info(format!("Forgot: {fact}"));
+
+        Ok(())
+    }
+
+    /// Save `prompt`, or the latest prompt if empty, as a favorite.
+    fn add_favorite(&mut self, prompt: &str) -> anyhow::Result<()> {
+        let prompt = match prompt {
+            "" => self
+                .last_prompt
+                .as_deref()
+                .context("No prompt sent yet, use /fav <prompt>")?,
+            prompt => prompt,
+        };
+
+        let number = self.favorites.add(prompt)?;
+        print_info(format!("Saved as favorite {number}."));
+
+        Ok(())
+    }
+
+    /// Save the conversation as checkpoint `name`, or list the checkpoints if empty.
+    fn checkpoint(&mut self, name: &str) {
+        if !name.is_empty() {
+            self.chat.checkpoint(name);
+            print_info(format!("Saved checkpoint {name:?}."));
+            return;
+        }
+
+        if self.chat.checkpoints().is_empty() {
+            print_info("No checkpoints. Save one with /checkpoint <name>.");
+        }
+        for name in self.chat.checkpoints() {
+            print_info(name);
+        }
+    }
+
+    /// Roll the conversation back to checkpoint `name`.
+    fn rollback(&mut self, name: &str) -> anyhow::Result<()> {
+        if !self.chat.rollback(name) {
+            return Err(anyhow!("No checkpoint {name:?}"));
+        }
+
+        print_info(format!(
+            "Rolled back to checkpoint {name:?}, {} exchanges in the context.",
+            self.chat.exchanges().count()
+        ));
+
+        Ok(())
+    }
+
+    /// List the favorite prompts, or resend favorite `number`.
+    async fn favorites(&mut self, number: &str) -> anyhow::Result<()> {
+        if number.is_empty() {
+            if self.favorites.prompts().is_empty() {
+                print_info("No favorites. Save the latest prompt with /fav.");
+            }
+
+            for (index, prompt) in self.favorites.prompts().iter().enumerate() {
+                print_info(format!("[{}] {}", index + 1, summary(prompt)));
+            }
+
+            return Ok(());
+        }
+
+        let number = number.parse().ok().context("Usage: /favs [N]")?;
+        let prompt = self
+            .favorites
+            .get(number)
+            .with_context(|| format!("No favorite {number}"))?
+            .to_string();
+
+        println!("{} {prompt}", "/favs:".bold().red());
+        self.ask(prompt).await;
+
+        Ok(())
+    }
+
+    /// Show the locale, or switch to `locale` for the following requests.
+    fn locale(&self, locale: &str) {
+        let mut preferences = self.preferences.lock().expect("not poisoned");
+
+        if locale.is_empty() {
+            match &preferences.locale {
+                Some(locale) => print_info(format!("Locale: {locale}.")),
+                None => print_info("No locale set."),
+            }
+        } else {
+            preferences.locale = Some(locale.to_string());
+            print_info(format!("Switched locale to {locale}."));
+        }
+    }
+
+    /// Show the incognito mode, or switch it `on` or `off` for the following exchanges.
+    fn incognito(&mut self, args: &str) -> anyhow::Result<()> {
+        match args {
+            "" => match self.chat.ephemeral() {
+                Some(turns) => print_info(format!(
+                    "Incognito mode is on, exchanges are forgotten after {turns} requests."
+                )),
+                None => print_info("Incognito mode is off."),
+            },
+            "on" => {
+                self.chat.set_ephemeral(Some(self.incognito_turns));
+                print_info(format!(
+                    "Incognito mode on: exchanges are not saved, and are forgotten after {} \
+                     requests.",
+                    self.incognito_turns
+                ));
+            }
+            "off" => {
+                self.chat.set_ephemeral(None);
+                print_info("Incognito mode off.");
+            }
+            _ => return Err(anyhow!("Usage: /incognito [on | off]")),
+        }
+
+        Ok(())
+    }
+
+    /// Save the latest exchange with the rating and an optional comment to the feedback file.
+    fn rate(&mut self, rating: Rating, comment: &str) -> anyhow::Result<()> {
+        let comment = comment.trim();
+        if self
+            .chat
+            .history()
+            .last()
+            .context("No exchange to rate yet")?
+            .is_ephemeral()
+        {
+            return Err(anyhow!(
+                "The latest exchange is incognito and can't be saved"
+            ));
+        }
+
+        let model = self.chat.model().to_string();
+        let exchange = self.chat.set_feedback(jutella::Feedback {
+            rating,
+            comment: (!comment.is_empty()).then(|| comment.to_string()),
+        })?;
+        Feedback::new(
+            &model,
+            (exchange.request(), exchange.response()),
+            rating,
+            (!comment.is_empty()).then_some(comment),
+        )
+        .append_to(&self.feedback_file)?;
+        print_info("Feedback saved.");
+
+        Ok(())
+    }
+
+    /// Manage stop sequences: `/stop` lists them, `/stop add "###"` adds one, `/stop clear`
+    /// removes all.
+    fn stop(&mut self, args: &str) -> anyhow::Result<()> {
+        const USAGE: &str = "Usage: /stop [add \"<sequence>\" | clear]";
+        const MAX_STOP_SEQUENCES: usize = 4;
+
+        let args = args.trim();
+
+        if args.is_empty() {
+            if self.chat.stop_sequences().is_empty() {
+                print_info("No stop sequences.");
+            }
+            for sequence in self.chat.stop_sequences() {
+                print_info(format!("{sequence:?}"));
+            }
+        } else if args == "clear" {
+            self.chat.set_stop_sequences(Vec::new());
+            print_info("Stop sequences cleared.");
+        } else if let Some(sequence) = args.strip_prefix("add ") {
+            let sequence = unquote(sequence.trim());
+            if sequence.is_empty() {
+                return Err(anyhow!(USAGE));
+            }
+
+            let mut stop = self.chat.stop_sequences().to_vec();
+            if stop.len() >= MAX_STOP_SEQUENCES {
+                return Err(anyhow!(
+                    "At most {MAX_STOP_SEQUENCES} stop sequences are supported"
+                ));
+            }
+            print_info(format!("Added stop sequence {sequence:?}."));
+            stop.push(sequence);
+            self.chat.set_stop_sequences(stop);
+        } else {
+            return Err(anyhow!(USAGE));
+        }
+
+        Ok(())
+    }
+
+    fn count(&self, text: &str) -> anyhow::Result<()> {
+        let tokens = self.chat.count_tokens(text)?;
+        print_info(format!("About {tokens} tokens."));
+
+        Ok(())
+    }
+
+    fn base64_size(&self, path: &str) -> anyhow::Result<()> {
+        let path = unquote(path);
+        let bytes = fs::read(&path).with_context(|| format!("Failed to read {path}"))?;
+        let encoded = estimate::base64(&bytes);
+        let tokens = self.chat.count_tokens(&encoded)?;
+
+        print_info(format!(
+            "{} bytes, {} bytes in base64, about {tokens} tokens as text.",
+            bytes.len(),
+            encoded.len(),
+        ));
+
+        Ok(())
+    }
+
+    fn cost(&self, prompt: &str) -> anyhow::Result<()> {
+        let tokens = self.chat.estimate_prompt_tokens(prompt)?;
+
+        match self.price.input {
+            Some(price) => print_info(format!(
+                "The prompt with the conversation is about {tokens} tokens, ${:.4} at \
+                 ${price} per million input tokens.",
+                estimate::cost(tokens, price),
+            )),
+            None => print_info(format!(
+                "The prompt with the conversation is about {tokens} tokens. Set `input_price` \
+                 in the config to estimate the cost."
+            )),
+        }
+
+        Ok(())
+    }
+}
+
+/// Strip optional surrounding double quotes and unescape `\n`.
+fn unquote(string: &str) -> String {
+    let string = string
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(string);
+
+    string.replace("\\n", "\n")
+}
+
+/// First line of the message, shortened for listings.
+fn summary(message: &str) -> String {
+    const MAX_CHARS: usize = 72;
+
+    let line = message.lines().next().unwrap_or_default();
+
+    if line.chars().count() > MAX_CHARS || message.lines().nth(1).is_some() {
+        format!("{}…", line.chars().take(MAX_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Exchange formatted the same way as printed during the session.
+fn format_exchange(request: &str, response: &str, hyperlinks: bool) -> String {
+    let response = if hyperlinks {
+        hyperlinks::linkify(response)
+    } else {
+        response.to_string()
+    };
+
+    format!(
+        "{} {}\n\n{} {response}\n\n",
+        "You:".bold().red(),
+        request.trim_end(),
+        "Assistant:".bold().green(),
+    )
+}
+
+/// Read a line from stdin without the trailing newline. Returns `None` at the end of input.
+fn read_line() -> Result<Option<String>, io::Error> {
+    let mut line = String::new();
+
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+
+    Ok(Some(line))
 }
 
 fn print_prompt() -> Result<(), io::Error> {
@@ -85,14 +872,72 @@ fn print_prompt() -> Result<(), io::Error> {
     io::stdout().flush()
 }
 
-fn print_response(response: &str) {
+fn print_response(response: &str, hyperlinks: bool) {
+    let response = if hyperlinks {
+        hyperlinks::linkify(response)
+    } else {
+        response.to_string()
+    };
+
     println!("\n{} {response}\n", "Assistant:".bold().green());
 }
 
+fn print_info(info: impl ToString) {
+    println!("{}", info.to_string().cyan());
+}
+
+fn print_warning(warning: Warning) {
+    eprintln!("{} {}", "Warning:".yellow(), warning.to_string().yellow());
+}
+
 fn print_error(e: impl ToString) {
     eprintln!("{} {}", "Error:".yellow(), e.to_string().yellow());
 }
 
+/// Pipe the text into `$PAGER`, `less -R` by default.
+fn run_pager(text: String) -> anyhow::Result<()> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| String::from("less -R"));
+    let mut args = pager.split_whitespace();
+    let program = args.next().expect("pager is not empty");
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to spawn pager `{pager}`"))?;
+
+    let mut stdin = child.stdin.take().context("Failed to open pager stdin")?;
+    // The pager may exit before reading everything, e.g., if the user quits early.
+    let _ = stdin.write_all(text.as_ref());
+    drop(stdin);
+
+    child.wait().context("Failed to wait for pager")?;
+
+    Ok(())
+}
+
+fn paste_from_clipboard() -> anyhow::Result<String> {
+    let output = Command::new("xclip")
+        .arg("-selection")
+        .arg("clipboard")
+        .arg("-o")
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run `xclip`")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to paste from clipboard: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    String::from_utf8(output.stdout).context("Clipboard contents are not valid UTF-8")
+}
+
 fn copy_to_clipboard(string: String) -> anyhow::Result<()> {
     let mut xclip = Command::new("xclip")
         .arg("-selection")
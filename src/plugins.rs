@@ -0,0 +1,215 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! CLI plugins: external `jutella-<command>` executables invoked as `/<command> [args]`.
+//!
+//! A plugin receives a JSON object with the command arguments and the conversation on stdin:
+//!
+//! ```json
+//! {
+//!   "args": "command arguments",
+//!   "conversation": [{ "request": "user message", "response": "assistant message" }]
+//! }
+//! ```
+//!
+//! and prints on stdout either `{ "type": "prompt", "content": "..." }` to send a new prompt
+//! to the model, or `{ "type": "display", "content": "..." }` to show a message to the user.
+//! Output that is not a JSON object is displayed as is.
+//!
+//! Ephemeral (incognito) exchanges are not passed to plugins.
+
+use anyhow::{anyhow, Context as _};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    io::{self, Write as _},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+};
+
+const PLUGIN_PREFIX: &str = "jutella-";
+
+/// Plugin input.
+#[derive(Serialize)]
+struct Input<'a> {
+    args: &'a str,
+    conversation: Vec<Exchange<'a>>,
+}
+
+#[derive(Serialize)]
+struct Exchange<'a> {
+    request: &'a str,
+    response: &'a str,
+}
+
+/// Plugin output.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "lowercase")]
+pub enum Output {
+    /// Prompt to send to the model.
+    Prompt(String),
+    /// Message to show to the user.
+    Display(String),
+    /// File to attach to the next prompt.
+    Attachment(String),
+}
+
+/// Split `/command args` into the command name and arguments. Returns `None` if the line
+/// doesn't look like a command, e.g., is a path.
+pub fn parse_command(line: &str) -> Option<(&str, &str)> {
+    let line = line.strip_prefix('/')?;
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    valid.then_some((name, args.trim()))
+}
+
+/// Whether plugin `jutella-<name>` is in `PATH`.
+pub fn is_installed(name: &str) -> bool {
+    let executable = format!("{PLUGIN_PREFIX}{name}");
+
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| is_executable(&dir.join(&executable)))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Serialized plugin input with the non-ephemeral exchanges of the `conversation`.
+fn input<'a>(
+    args: &str,
+    conversation: impl Iterator<Item = &'a jutella::Exchange>,
+) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&Input {
+        args,
+        conversation: conversation
+            .filter(|exchange| !exchange.is_ephemeral())
+            .map(|exchange| Exchange {
+                request: exchange.request(),
+                response: exchange.response(),
+            })
+            .collect(),
+    })
+}
+
+/// Run plugin `jutella-<name>` with the conversation so far.
+pub fn run<'a>(
+    name: &str,
+    args: &str,
+    conversation: impl Iterator<Item = &'a jutella::Exchange>,
+) -> anyhow::Result<Output> {
+    let executable = format!("{PLUGIN_PREFIX}{name}");
+
+    let mut plugin = match Command::new(&executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(plugin) => plugin,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(anyhow!(
+                "Unknown command `/{name}` (no `{executable}` in PATH)"
+            ))
+        }
+        Err(e) => return Err(e).context(format!("Failed to spawn `{executable}`")),
+    };
+
+    let input = input(args, conversation)?;
+
+    let mut stdin = plugin
+        .stdin
+        .take()
+        .context(format!("Failed to open `{executable}` stdin"))?;
+    // The input is written concurrently with reading the output, so a plugin writing before it
+    // has read all the input doesn't block on a full pipe.
+    let writer = thread::spawn(move || stdin.write_all(&input).and_then(|()| stdin.flush()));
+
+    let output = plugin
+        .wait_with_output()
+        .context(format!("Failed to wait for `{executable}`"))?;
+
+    match writer.join().expect("writer not to panic") {
+        // The plugin doesn't need to read all the input.
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            return Err(e).context(format!("Failed to pass conversation to `{executable}`"));
+        }
+        _ => {}
+    }
+
+    if !output.status.success() {
+        return Err(anyhow!("`{executable}` failed: {}", output.status));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .context(format!("`{executable}` output is not valid UTF-8"))?;
+
+    Ok(serde_json::from_str(&stdout).unwrap_or(Output::Display(stdout.trim_end().to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jutella::{Auth, ChatClient, ChatClientConfig, Provider};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn input_skips_ephemeral_exchanges() {
+        let mut chat = ChatClient::new(
+            Auth::None,
+            ChatClientConfig {
+                api_url: String::from("http://localhost:11434/"),
+                provider: Provider::Ollama,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        chat.push_exchange(String::from("Hi!"), String::from("Hello!"));
+        chat.set_ephemeral(Some(5));
+        chat.push_exchange(String::from("Secret?"), String::from("Secret!"));
+
+        let input: Value = serde_json::from_slice(&input("args", chat.history()).unwrap()).unwrap();
+        assert_eq!(
+            input,
+            json!({
+                "args": "args",
+                "conversation": [{ "request": "Hi!", "response": "Hello!" }],
+            })
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! User locale and unit preferences injected into the system message.
+
+use serde::Deserialize;
+use std::fmt;
+
+/// Measurement units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    Metric,
+    Imperial,
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Units::Metric => write!(f, "metric"),
+            Units::Imperial => write!(f, "imperial"),
+        }
+    }
+}
+
+/// Conversation-level preferences.
+#[derive(Debug, Default, Clone)]
+pub struct Preferences {
+    /// Locale tag, e.g., "de-DE".
+    pub locale: Option<String>,
+    pub units: Option<Units>,
+}
+
+impl Preferences {
+    /// System message extended with the instructions following the preferences.
+    pub fn system_message(&self, system_message: Option<String>) -> Option<String> {
+        let mut instructions = Vec::new();
+
+        if let Some(locale) = &self.locale {
+            instructions.push(format!(
+                "- Locale: {locale}. Answer in its language and use its date, time and number \
+                formats."
+            ));
+        }
+        if let Some(units) = self.units {
+            instructions.push(format!("- Units: use {units} units."));
+        }
+
+        if instructions.is_empty() {
+            return system_message;
+        }
+
+        let instructions = format!("User preferences:\n{}", instructions.join("\n"));
+
+        Some(match system_message {
+            Some(system_message) => format!("{system_message}\n\n{instructions}"),
+            None => instructions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences_instructions() {
+        assert_eq!(
+            Preferences::default().system_message(Some(String::from("Be brief."))),
+            Some(String::from("Be brief."))
+        );
+
+        let preferences = Preferences {
+            locale: Some(String::from("de-DE")),
+            units: Some(Units::Metric),
+        };
+
+        assert_eq!(
+            preferences.system_message(Some(String::from("Be brief."))),
+            Some(String::from(
+                "Be brief.\n\nUser preferences:\n\
+                - Locale: de-DE. Answer in its language and use its date, time and number formats.\n\
+                - Units: use metric units."
+            ))
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright (c) 2024 Dmitry Markin
+//
+// SPDX-License-Identifier: MIT
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Webhook notified of the finished exchanges.
+//!
+//! The summary of every exchange, or only of the failed ones, is POSTed as JSON:
+//!
+//! ```json
+//! {
+//!   "model": "gpt-4o-mini",
+//!   "request": "user message",
+//!   "response": "assistant message or null",
+//!   "error": "error message or null",
+//!   "tokens_in": 12,
+//!   "tokens_out": 34
+//! }
+//! ```
+
+use colored::Colorize as _;
+use jutella::{ExchangeHook, ExchangeSummary};
+use std::{sync::Arc, time::Duration};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exchange hook posting the summaries to `url` in the background.
+pub fn hook(url: String, errors_only: bool) -> ExchangeHook {
+    let client = reqwest::Client::new();
+
+    Arc::new(move |summary: ExchangeSummary| {
+        if errors_only && summary.error.is_none() {
+            return;
+        }
+
+        let request = client.post(&url).timeout(WEBHOOK_TIMEOUT).json(&summary);
+
+        tokio::spawn(async move {
+            if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                eprintln!(
+                    "{} {}",
+                    "Webhook error:".yellow(),
+                    e.without_url().to_string().yellow()
+                );
+            }
+        });
+    })
+}